@@ -1,16 +1,18 @@
 use std::{
+    collections::{HashMap, HashSet, VecDeque},
     io::{self, Write},
     path::{Path, PathBuf},
     sync::{
-        Arc,
+        Arc, OnceLock,
         atomic::{AtomicUsize, Ordering},
     },
-    time::Duration,
+    time::{Duration, UNIX_EPOCH},
 };
 
 use fs_err as fs;
 
 use color_eyre::eyre::bail;
+use colored::Colorize;
 use dialoguer::{Input, MultiSelect, Select, theme::ColorfulTheme};
 use fs::OpenOptions;
 use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
@@ -23,9 +25,29 @@ use ree_pak_core::{
     utf16_hash::Utf16HashExt, write::FileOptions,
 };
 
-use crate::{chunk::ChunkName, component::UpdateCheck, metadata::PakMetadata, util::human_bytes};
+use crate::{
+    backup::{self, BackupStore},
+    chunk::ChunkName,
+    component::UpdateCheck,
+    compression::{DEFAULT_ZSTD_LEVEL, EntryCompression},
+    diff::{self, ChangeKind, PakSet},
+    discovery::{self, SkipReason},
+    doctor::{self, HealthReport, PakClass, PakInfo},
+    estimate,
+    install::{self, Severity},
+    leftover::{Fix, Leftover},
+    metadata::{OutputKind, OutputPart, PakMetadata},
+    mods::{self, DetectedMod, ModSource, OverrideSource, Overrides},
+    name_list, pack,
+    patch_seq::{self, PatchFile, PatchKind, Rename},
+    profile::GameProfile,
+    record::{Baseline, BaselineChange, BaselineFile, Record},
+    sniff, steam,
+    tex_index::TexIndex,
+    unknown_hash::{self, UnknownHashReport},
+    util::{format_unix_time, human_bytes},
+};
 
-const FILE_NAME_LIST: &[u8] = include_bytes!("../assets/MHWs_STM_Release.list.zst");
 const AUTO_CHUNK_SELECTION_SIZE_THRESHOLD: usize = 50 * 1024 * 1024; // 50MB
 const FALSE_TRUE_SELECTION: [&str; 2] = ["False", "True"];
 
@@ -34,6 +56,13 @@ enum Mode {
     Automatic = 0,
     Manual = 1,
     Restore = 2,
+    UnknownHashReport = 3,
+    BuildNameList = 4,
+    RenumberPatches = 5,
+    Doctor = 6,
+    DecompressModPak = 7,
+    Pack = 8,
+    Diff = 9,
 }
 
 impl Mode {
@@ -42,11 +71,44 @@ impl Mode {
             0 => Ok(Mode::Automatic),
             1 => Ok(Mode::Manual),
             2 => Ok(Mode::Restore),
+            3 => Ok(Mode::UnknownHashReport),
+            4 => Ok(Mode::BuildNameList),
+            5 => Ok(Mode::RenumberPatches),
+            6 => Ok(Mode::Doctor),
+            7 => Ok(Mode::DecompressModPak),
+            8 => Ok(Mode::Pack),
+            9 => Ok(Mode::Diff),
             _ => bail!("Invalid mode index: {index}"),
         }
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+struct ProcessOptions {
+    /// Package all files, including non-tex files
+    full_package: bool,
+    /// Clone feature flags from the original entries
+    feature_clone: bool,
+    /// Detect textures by content if their names are unknown
+    sniff_textures: bool,
+    /// Compression of non-texture entries
+    compression: EntryCompression,
+    /// Mark the output as generated by this tool
+    write_metadata: bool,
+}
+
+/// Split the output into part files of limited size
+#[derive(Debug, Clone, Copy)]
+struct SplitOptions<'a> {
+    /// Estimated maximum size of each part
+    max_part_size: u64,
+    /// Logical output name shared by all parts
+    output_name: &'a str,
+    /// Entries overridden by patches loaded after the first part, the other
+    /// parts are loaded after those patches so they must not contain them
+    first_part_only: &'a HashSet<u64>,
+}
+
 #[derive(Clone)]
 struct ChunkSelection {
     chunk_name: ChunkName,
@@ -54,6 +116,19 @@ struct ChunkSelection {
     full_path: PathBuf,
 }
 
+impl ChunkSelection {
+    /// Follow a rename of the file, if any
+    fn follow_renames(&mut self, renames: &[Rename]) {
+        if let Some(rename) = renames.iter().find(|rename| rename.from == self.full_path)
+            && let Ok(chunk_name) =
+                ChunkName::try_from_str(&rename.to.file_name().unwrap().to_string_lossy())
+        {
+            self.chunk_name = chunk_name;
+            self.full_path = rename.to.clone();
+        }
+    }
+}
+
 impl std::fmt::Display for ChunkSelection {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{} ({})", self.chunk_name, human_bytes(self.file_size))?;
@@ -61,13 +136,125 @@ impl std::fmt::Display for ChunkSelection {
     }
 }
 
+/// A tool generated file that can be restored
+struct RestoreItem {
+    path: PathBuf,
+    metadata: PakMetadata,
+    file_size: u64,
+    /// Creation time from metadata, or the file modification time
+    created_at: Option<u64>,
+    /// Backup of the original file, replace mode only
+    backup: Option<BackupLocation>,
+}
+
+/// Where the original file of a replace mode output is kept
+enum BackupLocation {
+    /// `.pak.backup` next to the original file
+    Local(PathBuf),
+    /// In the backup store
+    Store,
+    Missing,
+}
+
+impl RestoreItem {
+    fn new(
+        game_dir: &Path,
+        path: PathBuf,
+        metadata: PakMetadata,
+        backup_store: Option<&BackupStore>,
+    ) -> color_eyre::Result<Self> {
+        let file_metadata = fs::metadata(&path)?;
+        let created_at = metadata.created_at().or_else(|| {
+            file_metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs())
+        });
+        let backup = metadata.is_full_package().then(|| {
+            let local_path = path.with_extension("pak.backup");
+            if local_path.is_file() {
+                BackupLocation::Local(local_path)
+            } else if backup_store.is_some_and(|store| {
+                store
+                    .entry(backup::relative_path(game_dir, &path))
+                    .is_some()
+            }) {
+                BackupLocation::Store
+            } else {
+                BackupLocation::Missing
+            }
+        });
+        Ok(Self {
+            path,
+            metadata,
+            file_size: file_metadata.len(),
+            created_at,
+            backup,
+        })
+    }
+}
+
+impl std::fmt::Display for RestoreItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mode = match (self.metadata.is_full_package(), self.metadata.compression()) {
+            (false, _) => "patch".to_string(),
+            (true, EntryCompression::Store) => "replace".to_string(),
+            (true, compression) => format!("replace, {compression}"),
+        };
+        let sources = self.metadata.sources();
+        let source = if sources.is_empty() {
+            "unknown source".to_string()
+        } else {
+            sources.join(", ")
+        };
+        write!(
+            f,
+            "{} [{mode}] from {source} ({}, {})",
+            self.path.file_name().unwrap().to_string_lossy(),
+            human_bytes(self.file_size),
+            self.created_at
+                .map(format_unix_time)
+                .unwrap_or_else(|| "unknown date".to_string()),
+        )?;
+        if let Some(part) = self.metadata.part() {
+            write!(
+                f,
+                " - part {}/{} of {}",
+                part.index + 1,
+                part.count,
+                part.output
+            )?;
+        }
+        match self.backup {
+            Some(BackupLocation::Store) => write!(f, " - backup in backup directory")?,
+            Some(BackupLocation::Missing) => write!(f, " - backup missing")?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
 #[derive(Default)]
 pub struct App {
-    filename_table: Option<FileNameTable>,
+    profile: GameProfile,
+    filename_table: OnceLock<FileNameTable>,
+    tex_index: OnceLock<TexIndex>,
 }
 
 impl App {
     pub async fn run(&mut self) -> color_eyre::Result<()> {
+        // Game profile selection
+        let profiles = GameProfile::discover();
+        if profiles.len() > 1 {
+            let selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select game")
+                .items(profiles.iter().map(|profile| &profile.name))
+                .default(0)
+                .interact()?;
+            self.profile = profiles.into_iter().nth(selection).unwrap();
+        }
+
         // Welcome message
         element! {
             View(
@@ -78,7 +265,7 @@ impl App {
                 border_color: Color::Cyan,
             ) {
                 Text(
-                    content: "Monster Hunter: Wilds - Texture Decompressor",
+                    content: format!("{} - Texture Decompressor", self.profile.display_name()),
                     color: Color::Green,
                     weight: Weight::Bold,
                     align: TextAlign::Center,
@@ -98,19 +285,21 @@ impl App {
         .render_loop()
         .await?;
 
-        element! {
-            Text(content: "Loading embedded file path list...")
-        }
-        .print();
-
-        // println!("Loading embedded file path list...");
-        let filename_table = FileNameTable::from_bytes(FILE_NAME_LIST)?;
-        self.filename_table = Some(filename_table);
-
         // Mode selection
         let mode = Select::with_theme(&ColorfulTheme::default())
             .with_prompt("Select mode")
-            .items(["Automatic", "Manual", "Restore"])
+            .items([
+                "Automatic",
+                "Manual",
+                "Restore",
+                "Unknown hash report",
+                "Build file name list",
+                "Renumber patches",
+                "Doctor",
+                "Decompress mod pak",
+                "Pack directory",
+                "Diff paks",
+            ])
             .default(0)
             .interact()?;
         let mode = Mode::from_index(mode)?;
@@ -119,252 +308,537 @@ impl App {
             Mode::Automatic => self.auto_mode(),
             Mode::Manual => self.manual_mode(),
             Mode::Restore => self.restore_mode(),
+            Mode::UnknownHashReport => self.unknown_hash_mode(),
+            Mode::BuildNameList => self.build_name_list_mode(),
+            Mode::RenumberPatches => self.renumber_mode(),
+            Mode::Doctor => self.doctor_mode(),
+            Mode::DecompressModPak => self.mod_pak_mode(),
+            Mode::Pack => self.pack_mode(),
+            Mode::Diff => self.diff_mode(),
         }
     }
 
-    fn filename_table(&self) -> &FileNameTable {
-        self.filename_table.as_ref().unwrap()
-    }
-
-    /// Scan for all pak files in the game directory, including DLC directory
-    fn scan_all_pak_files(&self, game_dir: &Path) -> color_eyre::Result<Vec<ChunkSelection>> {
-        let mut main_chunks = Vec::new();
-        let mut dlc_chunks = Vec::new();
+    /// Get the file name table, load it on first use
+    fn filename_table(&self) -> color_eyre::Result<&FileNameTable> {
+        if let Some(filename_table) = self.filename_table.get() {
+            return Ok(filename_table);
+        }
 
-        // Scan main game directory
-        self.scan_pak_files_in_dir(game_dir, &mut main_chunks)?;
+        println!("Loading file path list...");
+        let filename_table = FileNameTable::from_bytes(&self.profile.name_list_data()?)?;
+        Ok(self.filename_table.get_or_init(|| filename_table))
+    }
 
-        // Scan DLC directory if it exists
-        let dlc_dir = game_dir.join("dlc");
-        if dlc_dir.is_dir() {
-            self.scan_pak_files_in_dir(&dlc_dir, &mut dlc_chunks)?;
+    /// Get the tex hash index, load it from cache or build it on first use
+    fn tex_index(&self) -> color_eyre::Result<&TexIndex> {
+        if let Some(tex_index) = self.tex_index.get() {
+            return Ok(tex_index);
         }
 
-        // If both main and DLC have files, ask user which locations to process
-        let selected_locations = if !main_chunks.is_empty() && !dlc_chunks.is_empty() {
-            let locations = vec!["Main game directory", "DLC directory"];
+        println!("Loading tex file index...");
+        let tex_index = TexIndex::load_or_build(
+            &TexIndex::cache_path(&self.profile.id),
+            &self.profile.name_list_data()?,
+            &self.profile.tex_suffixes(),
+        )?;
+        Ok(self.tex_index.get_or_init(|| tex_index))
+    }
 
-            MultiSelect::with_theme(&ColorfulTheme::default())
-                .with_prompt("Select locations to process (Space to select, Enter to confirm)")
-                .items(&locations)
-                .defaults(&[true, true])
-                .interact()?
-        } else if !main_chunks.is_empty() {
-            vec![0]
-        } else if !dlc_chunks.is_empty() {
-            vec![1]
-        } else {
-            vec![]
+    /// Ask user for the game directory, detected Steam install is used as default
+    fn input_game_dir(&self) -> color_eyre::Result<PathBuf> {
+        let detected_dirs = match self.profile.steam_app_id {
+            Some(app_id) => steam::find_app_install_dirs(&steam::default_steam_roots(), app_id),
+            None => vec![],
         };
 
-        let mut all_chunks = Vec::new();
-        for &location_idx in &selected_locations {
-            match location_idx {
-                0 => all_chunks.extend(main_chunks.iter().cloned()),
-                1 => all_chunks.extend(dlc_chunks.iter().cloned()),
-                _ => {}
+        let default_dir = match detected_dirs.len() {
+            0 => std::env::current_dir()?,
+            1 => detected_dirs[0].clone(),
+            _ => {
+                let mut items = detected_dirs
+                    .iter()
+                    .map(|dir| dir.display().to_string())
+                    .collect::<Vec<_>>();
+                items.push("Other location".to_string());
+
+                let selection = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Multiple game installations found, select one")
+                    .items(&items)
+                    .default(0)
+                    .interact()?;
+                match detected_dirs.get(selection) {
+                    Some(dir) => dir.clone(),
+                    None => std::env::current_dir()?,
+                }
             }
+        };
+
+        let game_dir: String = Input::<String>::with_theme(&ColorfulTheme::default())
+            .show_default(true)
+            .default(default_dir.to_string_lossy().to_string())
+            .with_prompt(format!("Input {} directory path", self.profile.name))
+            .interact_text()
+            .unwrap()
+            .trim_matches(|c| c == '\"' || c == '\'')
+            .to_string();
+
+        let game_dir = PathBuf::from(game_dir);
+        if !game_dir.is_dir() {
+            bail!("game directory not exists.");
         }
-        all_chunks.sort_by(|a, b| a.chunk_name.cmp(&b.chunk_name));
 
-        Ok(all_chunks)
+        Ok(game_dir)
     }
 
-    /// Scan pak files in a specific directory
-    fn scan_pak_files_in_dir(
-        &self,
-        dir: &Path,
-        all_chunks: &mut Vec<ChunkSelection>,
-    ) -> color_eyre::Result<()> {
-        let entries = fs::read_dir(dir)?;
-        for entry in entries {
-            let entry = entry?;
-            if !entry.file_type()?.is_file() {
-                continue;
+    /// Validate game install, report issues and ask user whether to continue on warnings
+    fn check_game_install(&self, game_dir: &Path) -> color_eyre::Result<()> {
+        let report = install::validate_install(game_dir, &self.profile, self.tex_index()?)?;
+        for issue in &report.issues {
+            match issue.severity() {
+                Severity::Warning => println!("{}: {}", "Warning".yellow().bold(), issue),
+                Severity::Fatal => println!("{}: {}", "Error".red().bold(), issue),
             }
+        }
 
-            let file_name = entry.file_name().to_string_lossy().to_string();
-            let file_path = entry.path();
-
-            if !file_name.ends_with(".pak") {
-                continue;
+        match report.severity() {
+            Some(Severity::Fatal) => bail!("Invalid game directory: {}", game_dir.display()),
+            Some(Severity::Warning) => {
+                let proceed = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Game directory check found problems. Continue anyway?")
+                    .default(0)
+                    .items(FALSE_TRUE_SELECTION)
+                    .interact()
+                    .unwrap();
+                if proceed == 0 {
+                    bail!("Cancelled.");
+                }
             }
+            None => {}
+        }
 
-            let chunk_name = match ChunkName::try_from_str(&file_name) {
-                Ok(chunk_name) => chunk_name,
-                Err(e) => {
-                    println!("Invalid chunk name, skipped: {e}");
-                    continue;
-                }
-            };
+        Ok(())
+    }
 
-            let file_size = fs::metadata(&file_path)?.len();
-            all_chunks.push(ChunkSelection {
-                chunk_name,
-                file_size,
-                full_path: file_path,
-            });
+    /// Scan for all pak files in the search roots of the game directory
+    fn scan_all_pak_files(&self, game_dir: &Path) -> color_eyre::Result<Vec<ChunkSelection>> {
+        let discovery = discovery::discover(game_dir, &self.profile.discovery_options())?;
+        if let Some(summary) = discovery.skipped_summary() {
+            println!("{summary}");
         }
 
-        Ok(())
+        // If multiple locations have files, ask user which locations to process
+        let locations: Vec<usize> = (0..discovery.roots.len())
+            .filter(|&i| discovery.chunks.iter().any(|c| c.root_index == i))
+            .collect();
+        let selected_locations: Vec<usize> = if locations.len() > 1 {
+            let location_names = locations
+                .iter()
+                .map(|&i| match discovery.roots[i].strip_prefix(game_dir) {
+                    Ok(relative) if relative.as_os_str().is_empty() => {
+                        "Main game directory".to_string()
+                    }
+                    Ok(relative) => format!("{} directory", relative.display()),
+                    Err(_) => discovery.roots[i].display().to_string(),
+                })
+                .collect::<Vec<_>>();
+
+            MultiSelect::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select locations to process (Space to select, Enter to confirm)")
+                .items(&location_names)
+                .defaults(&vec![true; locations.len()])
+                .interact()?
+                .into_iter()
+                .map(|i| locations[i])
+                .collect()
+        } else {
+            locations
+        };
+
+        let all_chunks = discovery
+            .chunks
+            .into_iter()
+            .filter(|c| selected_locations.contains(&c.root_index))
+            .map(|c| ChunkSelection {
+                chunk_name: c.chunk_name,
+                file_size: c.file_size,
+                full_path: c.full_path,
+            })
+            .collect();
+
+        Ok(all_chunks)
     }
 
     fn process_chunk(
         &self,
-        filename_table: &FileNameTable,
-        input_path: &Path,
+        tex_index: &TexIndex,
+        input_paths: &[&Path],
         output_path: &Path,
-        use_full_package_mode: bool,
-        use_feature_clone: bool,
-    ) -> color_eyre::Result<()> {
-        println!("Processing chunk: {}", input_path.display());
-
-        let file = fs::File::open(input_path)?;
-        let mut reader = io::BufReader::new(file);
-
-        let pak_archive = ree_pak_core::read::read_archive(&mut reader)?;
-        let archive_reader = PakArchiveReader::new(reader, &pak_archive);
-        let archive_reader_mtx = Mutex::new(archive_reader);
+        options: ProcessOptions,
+        overrides: Option<&Overrides>,
+        split: Option<SplitOptions>,
+    ) -> color_eyre::Result<Vec<PathBuf>> {
+        let mut input_readers = Vec::new();
+        let mut input_archives = Vec::new();
+        for input_path in input_paths {
+            println!("Processing chunk: {}", input_path.display());
+            let mut reader = io::BufReader::new(fs::File::open(input_path)?);
+            input_archives.push(ree_pak_core::read::read_archive(&mut reader)?);
+            input_readers.push(reader);
+        }
+        let archive_readers = input_readers
+            .into_iter()
+            .zip(&input_archives)
+            .map(|(reader, archive)| Mutex::new(PakArchiveReader::new(reader, archive)))
+            .collect::<Vec<_>>();
+
+        // merged inputs are given in load order, later ones win
+        let last_inputs: HashMap<u64, usize> = input_archives
+            .iter()
+            .enumerate()
+            .flat_map(|(i, archive)| archive.entries().iter().map(move |entry| (entry.hash(), i)))
+            .collect();
+        let input_entries = input_archives
+            .iter()
+            .enumerate()
+            .flat_map(|(i, archive)| archive.entries().iter().map(move |entry| (i, entry)))
+            .filter(|(i, entry)| last_inputs[&entry.hash()] == *i);
 
         // filtered entries
-        let entries = if use_full_package_mode {
-            pak_archive.entries().iter().collect::<Vec<_>>()
+        let entries = if options.full_package {
+            input_entries.collect::<Vec<_>>()
         } else {
             println!("Filtering entries...");
-            pak_archive
-                .entries()
-                .iter()
-                .filter(|entry| is_tex_file(entry.hash(), filename_table))
-                .collect::<Vec<_>>()
+            let tex_entries = input_entries
+                .filter(|(_, entry)| tex_index.contains(entry.hash()))
+                .collect::<Vec<_>>();
+            let tex_count = tex_entries.len();
+            let entries = tex_entries
+                .into_iter()
+                .filter(|(_, entry)| {
+                    overrides.is_none_or(|overrides| overrides.keeps(entry.hash()))
+                })
+                .collect::<Vec<_>>();
+            if entries.len() < tex_count {
+                println!(
+                    "Skipped {} textures overridden by mods or other chunks",
+                    tex_count - entries.len()
+                );
+            }
+            entries
         };
 
-        // new pak archive
-        let out_file = OpenOptions::new()
-            .create(true)
-            .truncate(true)
-            .write(true)
-            .open(output_path)?;
-        // +1 for metadata
-        let mut pak_writer =
-            ree_pak_core::write::PakWriter::new(out_file, (entries.len() as u64) + 1);
+        // textures read from overriding mod paks instead of the input
+        let mut mod_pak_readers = Vec::new();
+        let mut mod_pak_archives = Vec::new();
+        if let Some(overrides) = overrides
+            && overrides.decompress_mod_textures
+        {
+            for path in overrides.paks() {
+                let mut reader = io::BufReader::new(fs::File::open(path)?);
+                mod_pak_archives.push(ree_pak_core::read::read_archive(&mut reader)?);
+                mod_pak_readers.push(reader);
+            }
+        }
+        let mod_archive_readers = mod_pak_readers
+            .into_iter()
+            .zip(&mod_pak_archives)
+            .map(|(reader, archive)| Mutex::new(PakArchiveReader::new(reader, archive)))
+            .collect::<Vec<_>>();
+        let mod_entries: HashMap<u64, (usize, &PakEntry)> = mod_pak_archives
+            .iter()
+            .enumerate()
+            .flat_map(|(i, archive)| archive.entries().iter().map(move |entry| (i, entry)))
+            .filter(|(i, entry)| {
+                overrides.and_then(|overrides| overrides.source(entry.hash()))
+                    == Some(OverrideSource::Pak(*i))
+            })
+            .map(|(i, entry)| (entry.hash(), (i, entry)))
+            .collect();
 
-        // write metadata
-        let metadata = PakMetadata::new(use_full_package_mode);
-        metadata.write_to_pak(&mut pak_writer)?;
+        let mut parts = match split {
+            Some(split) => {
+                println!("Splitting output...");
+                // entries overridden by later patches only take effect in the first part
+                let mut entries = entries;
+                entries.sort_by_key(|(_, entry)| !split.first_part_only.contains(&entry.hash()));
+                let sizes = entries
+                    .par_iter()
+                    .map(|&(input_index, entry)| -> color_eyre::Result<u64> {
+                        if !tex_index.contains(entry.hash()) {
+                            return Ok(entry.decompressed_size());
+                        }
+                        let mut entry_reader = {
+                            let mut archive_reader = archive_readers[input_index].lock();
+                            archive_reader.owned_entry_reader(entry.clone())?
+                        };
+                        Ok(estimate::read_decompressed_tex_size(&mut entry_reader)?
+                            .unwrap_or(entry.decompressed_size()))
+                    })
+                    .collect::<color_eyre::Result<Vec<_>>>()?;
+
+                let mut parts = Vec::new();
+                let mut rest = entries.as_slice();
+                for part_len in estimate::split_by_size(&sizes, split.max_part_size) {
+                    let (part, remaining) = rest.split_at(part_len);
+                    parts.push(part.to_vec());
+                    rest = remaining;
+                }
+                let mut dropped = 0;
+                for part in parts.iter_mut().skip(1) {
+                    let len = part.len();
+                    part.retain(|(_, entry)| !split.first_part_only.contains(&entry.hash()));
+                    dropped += len - part.len();
+                }
+                parts.retain(|part| !part.is_empty());
+                if dropped > 0 {
+                    println!(
+                        "Left {dropped} entries out of the extra parts, later official patches supply them"
+                    );
+                }
+                println!("Output is split into {} parts", parts.len());
+                parts
+            }
+            None => vec![entries],
+        };
+        if parts.is_empty() {
+            parts.push(Vec::new());
+        }
 
-        let pak_writer_mtx = Arc::new(Mutex::new(pak_writer));
+        let source_names = input_paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        let archive_readers = &archive_readers;
+        let mod_archive_readers = &mod_archive_readers;
+        let mod_entries = &mod_entries;
+
+        let mut part_paths = Vec::new();
+        for (part_index, entries) in parts.iter().enumerate() {
+            let part_path = if part_index == 0 {
+                output_path.to_path_buf()
+            } else {
+                let path = part_path(output_path, part_index);
+                part_paths.push(path.clone());
+                path
+            };
 
-        let bar = ProgressBar::new(entries.len() as u64);
-        bar.set_style(
-            ProgressStyle::default_bar()
-                .template("Bytes written: {msg}\n{pos}/{len} {wide_bar}")?,
-        );
-        bar.enable_steady_tick(Duration::from_millis(200));
-
-        let pak_writer_mtx1 = Arc::clone(&pak_writer_mtx);
-        let bar1 = bar.clone();
-        let bytes_written = AtomicUsize::new(0);
-        let err = entries
-            .par_iter()
-            .try_for_each(move |&entry| -> color_eyre::Result<()> {
-                let pak_writer_mtx = &pak_writer_mtx1;
-                let bar = &bar1;
-                // read raw tex file
-                // parse tex file
-                let mut entry_reader = {
-                    let mut archive_reader = archive_reader_mtx.lock();
-                    archive_reader.owned_entry_reader(entry.clone())?
+            // new pak archive
+            let out_file = OpenOptions::new()
+                .create(true)
+                .truncate(true)
+                .write(true)
+                .open(&part_path)?;
+            // +1 for metadata, if written
+            let entry_count = entries.len() as u64 + u64::from(options.write_metadata);
+            let mut pak_writer = ree_pak_core::write::PakWriter::new(out_file, entry_count);
+
+            // write metadata, only the first part replaces the original file
+            if options.write_metadata {
+                let metadata = PakMetadata::new(options.full_package && part_index == 0)
+                    .with_compression(options.compression);
+                let mut metadata = match source_names.as_slice() {
+                    [source_name] => metadata.with_source(source_name),
+                    _ => metadata.with_merged_sources(source_names.clone()),
                 };
-
-                if !is_tex_file(entry.hash(), filename_table) {
-                    // plain file, just copy
-                    let mut buf = vec![];
-                    std::io::copy(&mut entry_reader, &mut buf)?;
-                    let mut pak_writer = pak_writer_mtx.lock();
-                    let write_bytes = write_to_pak(
-                        &mut pak_writer,
-                        entry,
-                        entry.hash(),
-                        &buf,
-                        use_feature_clone,
-                    )?;
-                    bytes_written.fetch_add(write_bytes, Ordering::SeqCst);
-                } else {
-                    let mut tex = Tex::from_reader(&mut entry_reader)?;
-                    // decompress mipmaps
-                    tex.batch_decompress()?;
-
-                    let tex_bytes = tex.as_bytes()?;
-                    let mut pak_writer = pak_writer_mtx.lock();
-                    let write_bytes = write_to_pak(
-                        &mut pak_writer,
-                        entry,
-                        entry.hash(),
-                        &tex_bytes,
-                        use_feature_clone,
-                    )?;
-                    bytes_written.fetch_add(write_bytes, Ordering::SeqCst);
+                if let Some(split) = split {
+                    metadata = metadata.with_part(OutputPart {
+                        output: split.output_name.to_string(),
+                        index: part_index as u32,
+                        count: parts.len() as u32,
+                    });
                 }
+                metadata.write_to_pak(&mut pak_writer)?;
+            }
 
-                bar.inc(1);
-                if bar.position().is_multiple_of(100) {
-                    bar.set_message(
-                        HumanBytes(bytes_written.load(Ordering::SeqCst) as u64).to_string(),
-                    );
-                }
-                Ok(())
-            });
-        if let Err(e) = err {
-            eprintln!("Error occurred when processing tex: {e}");
-            eprintln!(
-                "The process terminated early, we'll save the current processed tex files to pak file."
+            let pak_writer_mtx = Arc::new(Mutex::new(pak_writer));
+
+            let bar = ProgressBar::new(entries.len() as u64);
+            bar.set_style(
+                ProgressStyle::default_bar()
+                    .template("Bytes written: {msg}\n{pos}/{len} {wide_bar}")?,
+            );
+            bar.enable_steady_tick(Duration::from_millis(200));
+
+            let pak_writer_mtx1 = Arc::clone(&pak_writer_mtx);
+            let bar1 = bar.clone();
+            let bytes_written = AtomicUsize::new(0);
+            let err = entries.par_iter().try_for_each(
+                move |&(input_index, entry)| -> color_eyre::Result<()> {
+                    let pak_writer_mtx = &pak_writer_mtx1;
+                    let bar = &bar1;
+                    // read raw tex file
+                    // parse tex file
+                    let (entry, mut entry_reader) = match mod_entries.get(&entry.hash()) {
+                        Some((i, mod_entry)) => {
+                            let mut archive_reader = mod_archive_readers[*i].lock();
+                            (
+                                *mod_entry,
+                                archive_reader.owned_entry_reader((*mod_entry).clone())?,
+                            )
+                        }
+                        None => {
+                            let mut archive_reader = archive_readers[input_index].lock();
+                            (entry, archive_reader.owned_entry_reader(entry.clone())?)
+                        }
+                    };
+
+                    if !tex_index.contains(entry.hash()) {
+                        // plain file, just copy
+                        let mut buf = vec![];
+                        std::io::copy(&mut entry_reader, &mut buf)?;
+                        // textures with unknown names are detected by content
+                        let mut compression = options.compression;
+                        if options.sniff_textures
+                            && sniff::detect_file_type(&buf) == Some("tex")
+                            && let Ok(tex_bytes) = decompress_tex(&buf)
+                        {
+                            buf = tex_bytes;
+                            compression = EntryCompression::Store;
+                        }
+                        let mut pak_writer = pak_writer_mtx.lock();
+                        let write_bytes = write_to_pak(
+                            &mut pak_writer,
+                            entry,
+                            entry.hash(),
+                            &buf,
+                            options.feature_clone,
+                            compression,
+                        )?;
+                        bytes_written.fetch_add(write_bytes, Ordering::SeqCst);
+                    } else {
+                        let mut buf = vec![];
+                        std::io::copy(&mut entry_reader, &mut buf)?;
+                        let tex_bytes = decompress_tex(&buf)?;
+                        let mut pak_writer = pak_writer_mtx.lock();
+                        let write_bytes = write_to_pak(
+                            &mut pak_writer,
+                            entry,
+                            entry.hash(),
+                            &tex_bytes,
+                            options.feature_clone,
+                            EntryCompression::Store,
+                        )?;
+                        bytes_written.fetch_add(write_bytes, Ordering::SeqCst);
+                    }
+
+                    bar.inc(1);
+                    if bar.position().is_multiple_of(100) {
+                        bar.set_message(
+                            HumanBytes(bytes_written.load(Ordering::SeqCst) as u64).to_string(),
+                        );
+                    }
+                    Ok(())
+                },
+            );
+            let failed = err.is_err();
+            if let Err(e) = err {
+                eprintln!("Error occurred when processing tex: {e}");
+                eprintln!(
+                    "The process terminated early, we'll save the current processed tex files to pak file."
+                );
+            }
+
+            match Arc::try_unwrap(pak_writer_mtx) {
+                Ok(pak_writer) => pak_writer.into_inner().finish()?,
+                Err(_) => panic!("Arc::try_unwrap failed"),
+            };
+
+            bar.finish();
+
+            if failed {
+                break;
+            }
+        }
+
+        Ok(part_paths)
+    }
+
+    /// Estimate output size of the input chunks, and make sure there is enough free space
+    fn check_free_space(
+        &self,
+        input_paths: &[&Path],
+        use_full_package_mode: bool,
+    ) -> color_eyre::Result<()> {
+        let tex_index = self.tex_index()?;
+
+        println!("Estimating output size...");
+        let mut total_size = 0;
+        for input_path in input_paths {
+            let input_size = fs::metadata(input_path)?.len();
+            let output_size =
+                estimate::estimate_output_size(input_path, tex_index, use_full_package_mode)?;
+            println!(
+                "   {}: {} -> {}",
+                input_path.file_name().unwrap().to_string_lossy(),
+                human_bytes(input_size),
+                human_bytes(output_size)
             );
+            total_size += output_size;
         }
 
-        match Arc::try_unwrap(pak_writer_mtx) {
-            Ok(pak_writer) => pak_writer.into_inner().finish()?,
-            Err(_) => panic!("Arc::try_unwrap failed"),
-        };
+        // outputs are written next to the input files
+        let mut available_space = u64::MAX;
+        for output_dir in input_paths.iter().filter_map(|path| path.parent()) {
+            let output_dir = if output_dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                output_dir
+            };
+            available_space = available_space.min(estimate::available_space(output_dir)?);
+        }
+
+        println!(
+            "Estimated output size: {}, available space: {}",
+            human_bytes(total_size),
+            human_bytes(available_space)
+        );
 
-        bar.finish();
+        if total_size > available_space {
+            bail!(
+                "Not enough free space, at least {} more is required.",
+                human_bytes(total_size - available_space)
+            );
+        }
+        if total_size + total_size / 10 > available_space {
+            let proceed = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Free space will be almost used up after processing. Continue?")
+                .default(0)
+                .items(FALSE_TRUE_SELECTION)
+                .interact()
+                .unwrap();
+            if proceed == 0 {
+                bail!("Cancelled due to low free space.");
+            }
+        }
 
         Ok(())
     }
 
     fn auto_mode(&mut self) -> color_eyre::Result<()> {
-        let current_dir = std::env::current_dir()?;
-
         wait_for_enter(
             r#"Check list:
 
 1. Your game is already updated to the latest version.
-2. Uninstalled all the mods, or the generated files will break mods.
+2. Mods will be checked later, uninstall them if warned, or the generated files will break mods.
 
 I'm sure I've checked the list, press Enter to continue"#,
         );
 
-        let game_dir: String = Input::<String>::with_theme(&ColorfulTheme::default())
-            .show_default(true)
-            .default(current_dir.to_string_lossy().to_string())
-            .with_prompt("Input MonsterHunterWilds directory path")
-            .interact_text()
-            .unwrap()
-            .trim_matches(|c| c == '\"' || c == '\'')
-            .to_string();
-
-        let game_dir = Path::new(&game_dir);
-        if !game_dir.is_dir() {
-            bail!("game directory not exists.");
-        }
+        let game_dir = self.input_game_dir()?;
+        let game_dir = game_dir.as_path();
+        self.check_game_install(game_dir)?;
 
         // scan for pak files in main game directory and DLC directory
-        let all_chunk_selections = self.scan_all_pak_files(game_dir)?;
+        let mut all_chunk_selections = self.scan_all_pak_files(game_dir)?;
 
         // show chunks for selection
-        // only show sub chunks
+        // only show sub chunks and DLC chunks
         let chunk_selections: Vec<&ChunkSelection> = all_chunk_selections
             .iter()
-            .filter(|chunk_selection| chunk_selection.chunk_name.sub_id().is_some())
+            .filter(|chunk_selection| {
+                let chunk_name = &chunk_selection.chunk_name;
+                chunk_name.sub_id().is_some() || chunk_name.dlc_id().is_some()
+            })
             .collect();
         if chunk_selections.is_empty() {
             bail!("No available pak files found.");
@@ -404,67 +878,332 @@ I'm sure I've checked the list, press Enter to continue"#,
             .unwrap();
         let use_replace_mode = use_replace_mode == 1;
 
+        // patch mode: the output is loaded after all patches by default, which
+        // overrides textures of mod patches
+        let choose_position = !use_replace_mode
+            && Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Output position in the load order")
+                .default(0)
+                .items([
+                    "After all patches",
+                    "Choose position for each chunk (moves patches above it)",
+                ])
+                .interact()
+                .unwrap()
+                == 1;
+
+        // patch mode: one output for all selected chunks in the same directory
+        let merge_outputs = !use_replace_mode
+            && selected_chunk_selections.len() > 1
+            && Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Output patches")
+                .default(0)
+                .items(["One patch per chunk", "One merged patch per directory"])
+                .interact()
+                .unwrap()
+                == 1;
+
+        // patch mode outputs contain textures only
+        let compression = if use_replace_mode {
+            select_compression()?
+        } else {
+            EntryCompression::Store
+        };
+
+        // extra parts are placed at the end of the patch sequence, which only
+        // works if the output is not placed below other patches
+        let max_part_size = if choose_position {
+            None
+        } else {
+            let max_part_size_gb: u64 = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt(
+                    "Maximum output file size in GB, larger outputs are split (0 for no limit)",
+                )
+                .default(0)
+                .interact_text()
+                .unwrap();
+            (max_part_size_gb > 0).then_some(max_part_size_gb * 1024 * 1024 * 1024)
+        };
+
+        let input_paths: Vec<&Path> = selected_chunk_selections
+            .iter()
+            .map(|cs| cs.full_path.as_path())
+            .collect();
+        self.check_free_space(&input_paths, use_replace_mode)?;
+        let mut record = self.load_record(game_dir)?;
+        let mut detected_mods = self.check_mods(game_dir, &record, &selected_chunk_selections)?;
+
+        // patch mode: textures supplied by mods loaded after the output take
+        // effect anyway, `Some(true)` to decompress the mod's textures instead
+        let override_mode = if !use_replace_mode && (!detected_mods.is_empty() || choose_position) {
+            let selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Textures supplied by mods")
+                .default(0)
+                .items([
+                    "Skip them, the mod's textures stay in effect",
+                    "Decompress the mod's textures into the output",
+                    "Ignore mods",
+                ])
+                .interact()
+                .unwrap();
+            match selection {
+                0 => Some(false),
+                1 => Some(true),
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        let mut backup_store = if use_replace_mode {
+            self.select_backup_store(game_dir, &mut record)?
+        } else {
+            None
+        };
+
         // all chunk names for patch ID tracking
         let mut all_chunk_names: Vec<ChunkName> = all_chunk_selections
             .iter()
             .map(|cs| cs.chunk_name.clone())
             .collect();
 
+        // owned, so they can follow patches moved by earlier jobs
+        let mut jobs: VecDeque<Vec<ChunkSelection>> = if merge_outputs {
+            let mut groups: Vec<Vec<ChunkSelection>> = Vec::new();
+            for &chunk_selection in &selected_chunk_selections {
+                match groups
+                    .iter_mut()
+                    .find(|group| group[0].full_path.parent() == chunk_selection.full_path.parent())
+                {
+                    Some(group) => group.push(chunk_selection.clone()),
+                    None => groups.push(vec![chunk_selection.clone()]),
+                }
+            }
+            for group in &mut groups {
+                group.sort_by(|a, b| a.chunk_name.cmp(&b.chunk_name));
+            }
+            groups.into()
+        } else {
+            selected_chunk_selections
+                .iter()
+                .map(|chunk_selection| vec![(*chunk_selection).clone()])
+                .collect()
+        };
+
         // start processing
-        for chunk_selection in selected_chunk_selections {
-            let chunk_path = &chunk_selection.full_path;
-            let chunk_name = &chunk_selection.chunk_name;
+        while let Some(mut inputs) = jobs.pop_front() {
+            // merged outputs are placed in the series of the last chunk
+            let chunk_name = inputs.last().unwrap().chunk_name.clone();
+            let chunk_dir = inputs
+                .last()
+                .unwrap()
+                .full_path
+                .parent()
+                .unwrap()
+                .to_path_buf();
+            let mut shifts = Vec::new();
 
             let output_path = if use_replace_mode {
                 // In replace mode, first generate a temporary decompressed file
-                chunk_path.with_extension("pak.temp")
+                inputs.last().unwrap().full_path.with_extension("pak.temp")
             } else {
                 // In patch mode
                 // Find the max patch id for the current chunk series
+                let series = chunk_name.series();
                 let max_patch_id = all_chunk_names
                     .iter()
-                    .filter(|c| {
-                        c.major_id() == chunk_name.major_id()
-                            && c.patch_id() == chunk_name.patch_id()
-                            && c.sub_id() == chunk_name.sub_id()
-                    })
-                    .filter_map(|c| c.sub_patch_id())
+                    .filter(|c| c.series() == series)
+                    .filter_map(|c| c.last_patch_id())
                     .max()
                     .unwrap_or(0);
 
-                let new_patch_id = max_patch_id + 1;
+                let mut new_patch_id = max_patch_id + 1;
+                if choose_position {
+                    let series_patches = self.series_patch_files(
+                        game_dir,
+                        &record,
+                        &all_chunk_selections,
+                        &chunk_dir,
+                        &series,
+                    )?;
+                    new_patch_id = select_patch_position(&series_patches, new_patch_id)?;
+                    shifts = patch_seq::plan_insert(&series_patches, new_patch_id)?;
+                }
 
                 // Create a new chunk name
-                let output_chunk_name = chunk_name.set_sub_patch(new_patch_id);
+                let output_chunk_name = chunk_name.with_patch(new_patch_id);
 
                 // Add the new patch to the chunk list so it can be found in subsequent processing
                 all_chunk_names.push(output_chunk_name.clone());
 
                 // Determine output directory based on original chunk location
-                let output_dir = chunk_path.parent().unwrap();
-                output_dir.join(output_chunk_name.to_string())
+                chunk_dir.join(output_chunk_name.to_string())
             };
 
             println!("Output patch file: {}", output_path.display());
-            self.process_chunk(
-                self.filename_table(),
-                chunk_path,
+            if !use_replace_mode {
+                for shift in &shifts {
+                    println!(
+                        "Move patch: {} -> {}",
+                        shift.from.display(),
+                        shift.to.file_name().unwrap().to_string_lossy()
+                    );
+                }
+                patch_seq::apply_renames(&shifts)?;
+                record.rename_outputs(
+                    game_dir,
+                    shifts
+                        .iter()
+                        .map(|shift| (shift.from.as_path(), shift.to.as_path())),
+                );
+
+                // recorded before writing, so an interrupted output can be detected
+                record.add_output(game_dir, &output_path, false);
+                record.set_shifted(
+                    game_dir,
+                    &output_path,
+                    shifts
+                        .iter()
+                        .map(|shift| (shift.from.as_path(), shift.to.as_path())),
+                );
+                record.save(game_dir)?;
+
+                // inputs, later jobs and mods may have been moved
+                for chunk_selection in all_chunk_selections
+                    .iter_mut()
+                    .chain(inputs.iter_mut())
+                    .chain(jobs.iter_mut().flatten())
+                {
+                    chunk_selection.follow_renames(&shifts);
+                }
+                for detected_mod in &mut detected_mods {
+                    if let ModSource::Pak(path) = &mut detected_mod.source
+                        && let Some(shift) = shifts.iter().find(|shift| shift.from == *path)
+                    {
+                        *path = shift.to.clone();
+                    }
+                }
+                for shift in &shifts {
+                    if let Ok(shifted_name) =
+                        ChunkName::try_from_str(&shift.to.file_name().unwrap().to_string_lossy())
+                    {
+                        all_chunk_names.push(shifted_name);
+                    }
+                }
+            }
+
+            let chunk_selection = inputs.last().unwrap();
+            let chunk_path = &chunk_selection.full_path;
+            let input_paths = inputs
+                .iter()
+                .map(|cs| cs.full_path.as_path())
+                .collect::<Vec<_>>();
+
+            let superseded = if inputs.len() > 1 {
+                self.superseded_hashes(&all_chunk_selections, &input_paths)?
+            } else {
+                HashSet::new()
+            };
+            let overrides = if override_mode.is_some() || !superseded.is_empty() {
+                let mut overrides = Overrides::new(override_mode == Some(true));
+                overrides.add_superseded(&superseded);
+                if override_mode.is_some() {
+                    // only mods loaded after the output override it, including
+                    // patches moved above it, loose files need a mod loader
+                    let output_chunk_name = ChunkName::try_from_str(
+                        &output_path.file_name().unwrap().to_string_lossy(),
+                    )?;
+                    let has_loader = !mods::find_loader_dlls(game_dir).is_empty();
+                    for detected_mod in &detected_mods {
+                        match &detected_mod.source {
+                            ModSource::Pak(path)
+                                if mods::is_loaded_after(path, &output_chunk_name) =>
+                            {
+                                overrides.add_pak(path, &detected_mod.hashes)
+                            }
+                            ModSource::Pak(_) => {}
+                            ModSource::LooseFiles(_) if has_loader => {
+                                overrides.add_loose_files(&detected_mod.hashes)
+                            }
+                            ModSource::LooseFiles(_) => {}
+                        }
+                    }
+                }
+                Some(overrides)
+            } else {
+                None
+            };
+            let first_part_only = if use_replace_mode && max_part_size.is_some() {
+                self.later_patch_hashes(game_dir, &record, &all_chunk_selections, chunk_selection)?
+            } else {
+                HashSet::new()
+            };
+            let output_name = if use_replace_mode {
+                chunk_path.file_name().unwrap().to_string_lossy()
+            } else {
+                output_path.file_name().unwrap().to_string_lossy()
+            };
+            let split = max_part_size.map(|max_part_size| SplitOptions {
+                max_part_size,
+                output_name: &output_name,
+                first_part_only: &first_part_only,
+            });
+            let extra_parts = self.process_chunk(
+                self.tex_index()?,
+                &input_paths,
                 &output_path,
-                use_replace_mode,
-                true,
+                ProcessOptions {
+                    full_package: use_replace_mode,
+                    feature_clone: true,
+                    sniff_textures: false,
+                    compression,
+                    write_metadata: true,
+                },
+                overrides.as_ref(),
+                split,
             )?;
 
             // In replace mode, backup the original file
             // and rename the temporary file to the original file name
             if use_replace_mode {
                 // Backup the original file
-                let backup_path = chunk_path.with_extension("pak.backup");
-                if backup_path.exists() {
-                    fs::remove_file(&backup_path)?;
+                if let Some(store) = &mut backup_store {
+                    println!("Backup original file to: {}", store.dir().display());
+                    store.backup(chunk_path, backup::relative_path(game_dir, chunk_path))?;
+                } else {
+                    let backup_path = chunk_path.with_extension("pak.backup");
+                    if backup_path.exists() {
+                        fs::remove_file(&backup_path)?;
+                    }
+                    fs::rename(chunk_path, &backup_path)?;
                 }
-                fs::rename(chunk_path, &backup_path)?;
                 // Rename the temporary file to the original file name
                 fs::rename(&output_path, chunk_path)?;
+                record.add_output(game_dir, chunk_path, true);
+                record.save(game_dir)?;
+            }
+
+            // extra parts are loaded after the output, at the end of the series
+            if !extra_parts.is_empty() {
+                let series = chunk_name.series();
+                let max_patch_id = all_chunk_names
+                    .iter()
+                    .filter(|c| c.series() == series)
+                    .filter_map(|c| c.last_patch_id())
+                    .max()
+                    .unwrap_or(0);
+                for (i, part_path) in extra_parts.iter().enumerate() {
+                    let part_chunk_name = chunk_name.with_patch(max_patch_id + 1 + i as u32);
+                    let part_target = chunk_path
+                        .parent()
+                        .unwrap()
+                        .join(part_chunk_name.to_string());
+                    println!("Output part file: {}", part_target.display());
+                    fs::rename(part_path, &part_target)?;
+                    record.add_output(game_dir, &part_target, false);
+                    all_chunk_names.push(part_chunk_name);
+                }
+                record.save(game_dir)?;
             }
             println!();
         }
@@ -505,63 +1244,310 @@ I'm sure I've checked the list, press Enter to continue"#,
             .unwrap();
         let use_feature_clone = use_feature_clone == 1;
 
+        let compression = if use_full_package_mode {
+            select_compression()?
+        } else {
+            EntryCompression::Store
+        };
+
+        self.check_free_space(&[input_path], use_full_package_mode)?;
+
         self.process_chunk(
-            self.filename_table(),
-            input_path,
+            self.tex_index()?,
+            &[input_path],
             &input_path.with_extension("uncompressed.pak"),
-            use_full_package_mode,
-            use_feature_clone,
+            ProcessOptions {
+                full_package: use_full_package_mode,
+                feature_clone: use_feature_clone,
+                sniff_textures: false,
+                compression,
+                write_metadata: true,
+            },
+            None,
+            None,
         )?;
 
         Ok(())
     }
 
-    fn restore_mode(&mut self) -> color_eyre::Result<()> {
-        let current_dir = std::env::current_dir()?;
-
-        let game_dir: String = Input::<String>::with_theme(&ColorfulTheme::default())
-            .show_default(true)
-            .default(current_dir.to_string_lossy().to_string())
-            .with_prompt("Input MonsterHunterWilds directory path")
+    fn mod_pak_mode(&mut self) -> color_eyre::Result<()> {
+        let input: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Input mod .pak file path")
             .interact_text()
             .unwrap()
             .trim_matches(|c| c == '\"' || c == '\'')
             .to_string();
 
-        let game_dir = Path::new(&game_dir);
-        if !game_dir.is_dir() {
-            bail!("game directory not exists.");
+        let input_path = Path::new(&input);
+        if !input_path.is_file() {
+            bail!("input file not exists.");
         }
 
+        // mods following the chunk naming are loaded by name, keep it in another directory
+        let file_name = input_path.file_name().unwrap().to_string_lossy();
+        let default_output = if ChunkName::try_from_str(&file_name).is_ok() {
+            input_path.with_file_name("uncompressed").join(&*file_name)
+        } else {
+            input_path.with_extension("uncompressed.pak")
+        };
+        let output: String = Input::with_theme(&ColorfulTheme::default())
+            .show_default(true)
+            .default(default_output.to_string_lossy().to_string())
+            .with_prompt("Output .pak file path")
+            .interact_text()
+            .unwrap()
+            .trim_matches(|c| c == '\"' || c == '\'')
+            .to_string();
+        let output_path = Path::new(&output);
+        if output_path == input_path {
+            bail!("output file must be different from the input file.");
+        }
+        if let Some(parent) = output_path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+
+        let compression = select_compression()?;
+
+        self.check_free_space(&[input_path], true)?;
+
+        self.process_chunk(
+            self.tex_index()?,
+            &[input_path],
+            output_path,
+            ProcessOptions {
+                full_package: true,
+                feature_clone: true,
+                sniff_textures: true,
+                compression,
+                // the output is still a mod, not a tool output to restore
+                write_metadata: false,
+            },
+            None,
+            None,
+        )?;
+
+        println!("Saved to {}", output_path.display());
+        Ok(())
+    }
+
+    fn pack_mode(&mut self) -> color_eyre::Result<()> {
+        let input: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Input directory containing natives")
+            .interact_text()
+            .unwrap()
+            .trim_matches(|c| c == '\"' || c == '\'')
+            .to_string();
+
+        let input_dir = Path::new(&input);
+        if !input_dir.is_dir() {
+            bail!("input directory not exists.");
+        }
+
+        let files = pack::collect_files(input_dir)?;
+        if files.is_empty() {
+            bail!("No files found in the directory.");
+        }
+        let non_natives = files
+            .iter()
+            .filter(|file| !file.pak_path.starts_with("natives/"))
+            .count();
+        if non_natives > 0 {
+            println!(
+                "{}: {non_natives} files are not under natives/, the game won't find them",
+                "Warning".yellow().bold()
+            );
+        }
+
+        let tex_count = files.iter().filter(|file| file.is_tex()).count();
+        let decompress_textures = tex_count > 0
+            && Select::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("Decompress {tex_count} tex files?"))
+                .default(1)
+                .items(FALSE_TRUE_SELECTION)
+                .interact()
+                .unwrap()
+                == 1;
+
+        let output: String = Input::with_theme(&ColorfulTheme::default())
+            .show_default(true)
+            .default(
+                input_dir
+                    .with_extension("pak")
+                    .to_string_lossy()
+                    .to_string(),
+            )
+            .with_prompt("Output .pak file path")
+            .interact_text()
+            .unwrap()
+            .trim_matches(|c| c == '\"' || c == '\'')
+            .to_string();
+
+        let out_file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(&output)?;
+        // +1 for metadata
+        let mut pak_writer =
+            ree_pak_core::write::PakWriter::new(out_file, (files.len() as u64) + 1);
+
+        let metadata = PakMetadata::new(false)
+            .with_kind(OutputKind::Packed)
+            .with_source(input_dir.file_name().unwrap_or_default().to_string_lossy());
+        metadata.write_to_pak(&mut pak_writer)?;
+
+        let bar = ProgressBar::new(files.len() as u64);
+        bar.set_style(ProgressStyle::default_bar().template("{msg}\n{pos}/{len} {wide_bar}")?);
+        let mut bytes_written = 0;
+        for file in &files {
+            bar.set_message(file.pak_path.clone());
+            let mut data = fs::read(&file.full_path)?;
+            if decompress_textures && file.is_tex() {
+                match decompress_tex(&data) {
+                    Ok(tex_bytes) => data = tex_bytes,
+                    Err(e) => bar.println(format!(
+                        "{}: failed to decompress {}, packed as is: {e}",
+                        "Warning".yellow().bold(),
+                        file.pak_path
+                    )),
+                }
+            }
+
+            pak_writer.start_file(file.pak_path.as_str(), FileOptions::default())?;
+            pak_writer.write_all(&data)?;
+            bytes_written += data.len() as u64;
+            bar.inc(1);
+        }
+        pak_writer.finish()?;
+        bar.finish_and_clear();
+
+        println!(
+            "Packed {} files ({}) to {}",
+            files.len(),
+            human_bytes(bytes_written),
+            output
+        );
+        Ok(())
+    }
+
+    fn diff_mode(&mut self) -> color_eyre::Result<()> {
+        let old_paks = self.input_diff_side("Old pak file or game directory")?;
+        let new_paks = self.input_diff_side("New pak file or game directory")?;
+
+        let output: String = Input::with_theme(&ColorfulTheme::default())
+            .show_default(true)
+            .default("pak_diff.json".to_string())
+            .with_prompt("Output report file path (.json or .csv)")
+            .interact_text()
+            .unwrap()
+            .trim_matches(|c| c == '\"' || c == '\'')
+            .to_string();
+
+        println!("Reading pak entries...");
+        let old_set = PakSet::load(&old_paks)?;
+        let new_set = PakSet::load(&new_paks)?;
+        println!(
+            "Comparing {} old entries with {} new entries...",
+            old_set.len(),
+            new_set.len()
+        );
+        let report = diff::diff(&old_set, &new_set, self.filename_table()?)?;
+
+        let output_path = Path::new(&output);
+        let is_csv = output_path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+        if is_csv {
+            report.write_csv(output_path)?;
+        } else {
+            report.write_json(output_path)?;
+        }
+
+        for kind in [ChangeKind::Added, ChangeKind::Removed, ChangeKind::Changed] {
+            println!(
+                "{kind}: {} entries, {} textures",
+                report.count(kind),
+                report.texture_count(kind)
+            );
+        }
+        println!("Report saved to {output}");
+        Ok(())
+    }
+
+    /// Input a pak file, or a directory whose chunks are compared as a whole
+    fn input_diff_side(&self, prompt: &str) -> color_eyre::Result<Vec<PathBuf>> {
+        let input: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .interact_text()
+            .unwrap()
+            .trim_matches(|c| c == '\"' || c == '\'')
+            .to_string();
+
+        let input_path = PathBuf::from(input);
+        if input_path.is_file() {
+            return Ok(vec![input_path]);
+        }
+        if !input_path.is_dir() {
+            bail!("{} not exists.", input_path.display());
+        }
+
+        // chunks are sorted by name, which is also the load order
+        let discovery = discovery::discover(&input_path, &self.profile.discovery_options())?;
+        if discovery.chunks.is_empty() {
+            bail!("No pak files found in {}", input_path.display());
+        }
+        Ok(discovery
+            .chunks
+            .into_iter()
+            .map(|chunk| chunk.full_path)
+            .collect())
+    }
+
+    fn restore_mode(&mut self) -> color_eyre::Result<()> {
+        let game_dir = self.input_game_dir()?;
+        let game_dir = game_dir.as_path();
+        self.check_game_install(game_dir)?;
+
         // scan all pak files, find files generated by this tool
         println!("Scanning tool generated files...");
         let mut tool_generated_files = Vec::new();
         let mut backup_files = Vec::new();
+        let mut temp_files = Vec::new();
         let mut all_chunks = Vec::new();
-
-        // Scan main directory
-        self.scan_tool_files_in_directory(
+        self.scan_tool_files(
             game_dir,
             &mut tool_generated_files,
             &mut backup_files,
+            &mut temp_files,
             &mut all_chunks,
         )?;
 
-        // Scan DLC directory if exists
-        let dlc_dir = game_dir.join("dlc");
-        if dlc_dir.is_dir() {
-            self.scan_tool_files_in_directory(
-                &dlc_dir,
-                &mut tool_generated_files,
-                &mut backup_files,
-                &mut all_chunks,
-            )?;
-        }
+        let mut record = self.load_record(game_dir)?;
 
-        if tool_generated_files.is_empty() && backup_files.is_empty() {
-            println!("No files found to restore.");
-            return Ok(());
+        // files left by interrupted or outdated runs
+        let leftovers = self.find_leftovers(game_dir, &record, &backup_files, &temp_files)?;
+        if !leftovers.is_empty() {
+            println!("Found {} leftover files", leftovers.len());
         }
+        for leftover in &leftovers {
+            println!("{leftover}");
+            let fixes = leftover.fixes();
+            let fix = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select action")
+                .items(fixes)
+                .default(0)
+                .interact()?;
+            let fix = fixes[fix];
+            leftover.apply(fix)?;
+
+            // the live file is now either the original or gone
+            if fix != Fix::Keep && !matches!(leftover, Leftover::TempFile { .. }) {
+                record.remove_output(game_dir, &leftover.live_path());
+            }
+        }
+        record.save(game_dir)?;
 
         println!(
             "Found {} tool generated files and {} backup files",
@@ -569,34 +1555,134 @@ I'm sure I've checked the list, press Enter to continue"#,
             backup_files.len()
         );
 
+        let mut backup_store = self.open_backup_store(game_dir, &record)?;
+
+        // placeholders are cleaned up by compaction, don't list them
+        let mut restore_items = Vec::new();
+        for (file_path, metadata) in tool_generated_files {
+            if !metadata.is_full_package()
+                && self.check_patch_kind(game_dir, &record, &file_path)? == PatchKind::Placeholder
+            {
+                continue;
+            }
+            restore_items.push(RestoreItem::new(
+                game_dir,
+                file_path,
+                metadata,
+                backup_store.as_ref(),
+            )?);
+        }
+        if restore_items.is_empty() {
+            println!("No files found to restore.");
+            // placeholders left by previous restores may still be there
+            self.compact_patches(game_dir, &mut record)?;
+            return record.save(game_dir);
+        }
+
+        let selected_items: Option<Vec<usize>> = MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt("Select files to restore (Space to select, Enter to confirm)")
+            .items(&restore_items)
+            .defaults(&vec![true; restore_items.len()])
+            .interact_opt()?;
+        let Some(mut selected_items) = selected_items else {
+            bail!("No files selected.");
+        };
+
+        // parts of a split output are restored together
+        let selected_outputs: HashSet<&str> = selected_items
+            .iter()
+            .filter_map(|i| restore_items[*i].metadata.part())
+            .map(|part| part.output.as_str())
+            .collect();
+        for (i, item) in restore_items.iter().enumerate() {
+            if !selected_items.contains(&i)
+                && item
+                    .metadata
+                    .part()
+                    .is_some_and(|part| selected_outputs.contains(part.output.as_str()))
+            {
+                println!("Also restore part file: {}", item.path.display());
+                selected_items.push(i);
+            }
+        }
+        selected_items.sort();
+
         // restore
         let mut patch_files_to_remove = Vec::new();
-        for (file_path, metadata) in &tool_generated_files {
-            if metadata.is_full_package() {
-                // restore full package mode (replace mode)
-                // this is a replace mode generated file, find the corresponding backup file
-                let backup_path = file_path.with_extension("pak.backup");
-                if backup_path.exists() {
+        for item in selected_items.iter().map(|i| &restore_items[*i]) {
+            let file_path = &item.path;
+            match &item.backup {
+                Some(BackupLocation::Local(backup_path)) => {
+                    // restore full package mode (replace mode)
+                    // this is a replace mode generated file, restore the corresponding backup file
                     println!("Restore replace mode file: {}", file_path.display());
 
                     // delete the current file and restore the backup
                     fs::remove_file(file_path)?;
-                    fs::rename(&backup_path, file_path)?;
+                    fs::rename(backup_path, file_path)?;
+                    record.remove_output(game_dir, file_path);
 
                     println!("   Restore backup file: {}", backup_path.display());
-                } else {
-                    println!("Warning: backup file not found {}", backup_path.display());
                 }
-            } else {
-                // restore patch mode
-                // this is a patch mode generated file
-                if let Ok(chunk_name) =
-                    ChunkName::try_from_str(&file_path.file_name().unwrap().to_string_lossy())
-                {
-                    patch_files_to_remove.push((file_path.clone(), chunk_name));
+                Some(BackupLocation::Store) => {
+                    let store = backup_store.as_mut().unwrap();
+                    println!("Restore replace mode file: {}", file_path.display());
+                    // the backup is verified before replacing the current file
+                    match store.restore(game_dir, backup::relative_path(game_dir, file_path)) {
+                        Ok(()) => {
+                            record.remove_output(game_dir, file_path);
+                            println!("   Restore backup file from: {}", store.dir().display());
+                        }
+                        Err(e) => println!("{}: {:#}", "Error".red().bold(), e),
+                    }
+                }
+                Some(BackupLocation::Missing) => {
+                    println!("Warning: backup file not found for {}", file_path.display());
+                }
+                None if record.is_placed(game_dir, file_path) => {
+                    // placed below other patches, move them back down
+                    println!("Remove patch file: {}", file_path.display());
+                    fs::remove_file(file_path)?;
+                    let output = record.output(game_dir, file_path).cloned().unwrap();
+                    record.remove_output(game_dir, file_path);
+
+                    let mut undo_renames = Vec::new();
+                    for shift in &output.shifted {
+                        let from = game_dir.join(&shift.from);
+                        let to = game_dir.join(&shift.to);
+                        if to.is_file() {
+                            undo_renames.push(Rename { from: to, to: from });
+                        } else {
+                            println!(
+                                "{}: {} no longer exists, the patch sequence may have a gap",
+                                "Warning".yellow().bold(),
+                                shift.to
+                            );
+                        }
+                    }
+                    patch_seq::apply_renames(&undo_renames)?;
+                    record.rename_outputs(
+                        game_dir,
+                        undo_renames
+                            .iter()
+                            .map(|rename| (rename.from.as_path(), rename.to.as_path())),
+                    );
+                    println!("   Moved {} patches back", undo_renames.len());
+                }
+                None => {
+                    // restore patch mode
+                    // this is a patch mode generated file
+                    if let Ok(chunk_name) =
+                        ChunkName::try_from_str(&file_path.file_name().unwrap().to_string_lossy())
+                    {
+                        patch_files_to_remove.push((file_path.clone(), chunk_name));
+                    }
                 }
             }
         }
+        if backup_store.as_ref().is_some_and(|store| store.is_empty()) {
+            record.backup_dir = None;
+        }
 
         // remove patch files
         if !patch_files_to_remove.is_empty() {
@@ -606,16 +1692,9 @@ I'm sure I've checked the list, press Enter to continue"#,
                 println!("Remove patch file: {}", file_path.display());
 
                 // Check if there are any patches with higher numbers
+                let series = chunk_name.series();
                 let has_higher_patches = all_chunks.iter().any(|c| {
-                    c.major_id() == chunk_name.major_id()
-                        && c.sub_id() == chunk_name.sub_id()
-                        && match (c.sub_id(), c.sub_patch_id()) {
-                            (Some(_), Some(patch_id)) => {
-                                patch_id > chunk_name.sub_patch_id().unwrap()
-                            }
-                            (None, Some(patch_id)) => patch_id > chunk_name.patch_id().unwrap(),
-                            _ => false,
-                        }
+                    c.series() == series && c.last_patch_id() > chunk_name.last_patch_id()
                 });
 
                 if has_higher_patches {
@@ -625,6 +1704,7 @@ I'm sure I've checked the list, press Enter to continue"#,
                 } else {
                     // no higher patches exist, safe to delete
                     fs::remove_file(file_path)?;
+                    record.remove_output(game_dir, file_path);
                     // remove from all_chunks
                     all_chunks.retain(|c| c != chunk_name);
                     println!("   Removed patch file");
@@ -632,61 +1712,901 @@ I'm sure I've checked the list, press Enter to continue"#,
             }
         }
 
+        // clean up placeholders that are no longer needed
+        self.compact_patches(game_dir, &mut record)?;
+
+        record.save(game_dir)?;
         println!("Restore completed!");
         Ok(())
     }
 
-    /// Scan tool generated files in a specific directory
-    fn scan_tool_files_in_directory(
+    fn renumber_mode(&mut self) -> color_eyre::Result<()> {
+        let game_dir = self.input_game_dir()?;
+        let game_dir = game_dir.as_path();
+        let mut record = self.load_record(game_dir)?;
+
+        // clear placeholders first, so renumbered patches don't collide with them
+        self.compact_patches(game_dir, &mut record)?;
+        let patches = self.scan_patch_files(game_dir, &record)?;
+
+        // generated files overwritten by game updates have lost their metadata
+        let mut stale_outputs = Vec::new();
+        for output in &record.outputs {
+            let path = output.full_path(game_dir);
+            if !path.is_file() {
+                stale_outputs.push(path);
+            } else if self.check_tool_generated_file(&path)?.is_none() {
+                println!(
+                    "{}: generated file {} has been replaced by an official file, run Automatic mode again to regenerate it",
+                    "Warning".yellow().bold(),
+                    output.path
+                );
+                stale_outputs.push(path);
+            }
+        }
+        for path in &stale_outputs {
+            record.remove_output(game_dir, path);
+        }
+
+        let (renames, placeholders) = patch_seq::plan_renumber(&patches);
+        if renames.is_empty() {
+            record.save(game_dir)?;
+            println!("Generated patches are already above official patches, nothing to renumber.");
+            return Ok(());
+        }
+
+        for rename in &renames {
+            println!(
+                "{} -> {}",
+                rename.from.file_name().unwrap().to_string_lossy(),
+                rename.to.file_name().unwrap().to_string_lossy()
+            );
+        }
+        for placeholder in &placeholders {
+            println!(
+                "{} (placeholder)",
+                placeholder.file_name().unwrap().to_string_lossy()
+            );
+        }
+        let confirm = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Renumber {} patch files?", renames.len()))
+            .default(1)
+            .items(FALSE_TRUE_SELECTION)
+            .interact()
+            .unwrap();
+        if confirm == 0 {
+            bail!("Cancelled.");
+        }
+
+        patch_seq::apply_renames(&renames)?;
+        record.rename_outputs(
+            game_dir,
+            renames
+                .iter()
+                .map(|rename| (rename.from.as_path(), rename.to.as_path())),
+        );
+        record.save(game_dir)?;
+        // keep the sequence continuous below official patches
+        for placeholder in &placeholders {
+            self.create_empty_patch_file(placeholder)?;
+        }
+
+        println!("Renumbered {} patch files.", renames.len());
+        Ok(())
+    }
+
+    fn doctor_mode(&mut self) -> color_eyre::Result<()> {
+        let game_dir = self.input_game_dir()?;
+        let game_dir = game_dir.as_path();
+        let mut report = HealthReport::default();
+
+        let install_report = install::validate_install(game_dir, &self.profile, self.tex_index()?)?;
+        for issue in &install_report.issues {
+            report.add(
+                issue.severity(),
+                issue.to_string(),
+                "Check the game directory, or verify game files in Steam",
+            );
+        }
+
+        let record = self.load_record(game_dir)?;
+
+        // inventory
+        println!("Scanning pak files...");
+        let discovery = discovery::discover(game_dir, &self.profile.discovery_options())?;
+        let mut patches = Vec::new();
+        let mut current_files = Vec::new();
+        for chunk in &discovery.chunks {
+            let kind = self.check_patch_kind(game_dir, &record, &chunk.full_path)?;
+            // generated files are tracked by the record, placeholders come and go
+            if !record.is_tracked(game_dir, &chunk.full_path) && kind != PatchKind::Placeholder {
+                current_files.push(BaselineFile::new(
+                    game_dir,
+                    &chunk.full_path,
+                    chunk.file_size,
+                    kind == PatchKind::Generated,
+                ));
+            }
+            report.paks.push(PakInfo {
+                path: chunk.full_path.clone(),
+                class: match kind {
+                    PatchKind::Official => PakClass::Official,
+                    PatchKind::ThirdParty => PakClass::ThirdParty,
+                    PatchKind::Generated => PakClass::Generated,
+                    PatchKind::Placeholder => PakClass::Placeholder,
+                },
+                size: chunk.file_size,
+            });
+            if chunk.chunk_name.is_patch() {
+                patches.push(PatchFile {
+                    chunk_name: chunk.chunk_name.clone(),
+                    path: chunk.full_path.clone(),
+                    // placed outputs are intentionally below other patches
+                    kind: if record.is_placed(game_dir, &chunk.full_path) {
+                        PatchKind::Official
+                    } else {
+                        kind
+                    },
+                });
+            }
+        }
+        for skipped in &discovery.skipped {
+            if matches!(skipped.reason, SkipReason::InvalidChunkName(_)) {
+                report.paks.push(PakInfo {
+                    path: skipped.path.clone(),
+                    class: PakClass::ThirdParty,
+                    size: fs::metadata(&skipped.path).map_or(0, |m| m.len()),
+                });
+            }
+        }
+        let mut backup_files = Vec::new();
+        let mut temp_files = Vec::new();
+        for root in &discovery.roots {
+            for entry in fs::read_dir(root)? {
+                let path = entry?.path();
+                let file_name = path.file_name().unwrap().to_string_lossy();
+                let class = if file_name.ends_with(".pak.backup") {
+                    backup_files.push(path.clone());
+                    PakClass::Backup
+                } else if file_name.ends_with(".pak.temp") {
+                    temp_files.push(path.clone());
+                    PakClass::Temp
+                } else {
+                    continue;
+                };
+                report.paks.push(PakInfo {
+                    size: fs::metadata(&path)?.len(),
+                    path,
+                    class,
+                });
+            }
+        }
+
+        // checks
+        report
+            .findings
+            .extend(doctor::check_patch_sequences(&patches));
+
+        for leftover in self.find_leftovers(game_dir, &record, &backup_files, &temp_files)? {
+            report.add(Severity::Warning, leftover.to_string(), "Run Restore");
+        }
+        for output in &record.outputs {
+            let path = output.full_path(game_dir);
+            if path.is_file()
+                && is_valid_pak(&path)
+                && self.check_tool_generated_file(&path)?.is_none()
+            {
+                report.add(
+                    Severity::Warning,
+                    format!(
+                        "generated file {} has been replaced by an official file",
+                        output.path
+                    ),
+                    "Run Renumber patches, then Automatic mode to regenerate it",
+                );
+            }
+        }
+
+        if let Some(baseline) = &record.baseline {
+            for change in baseline.changes(&current_files) {
+                // removed generated files are reported as leftovers if needed
+                if record.is_tracked(game_dir, &game_dir.join(change.path()))
+                    || matches!(&change, BaselineChange::Missing(file) if file.has_metadata)
+                {
+                    continue;
+                }
+                let suggestion = match change {
+                    BaselineChange::Added(_) => "Check if it's a mod you installed",
+                    BaselineChange::Missing(_) | BaselineChange::Changed { .. } => {
+                        "Verify game files in Steam if this wasn't a game update"
+                    }
+                };
+                report.add(
+                    Severity::Warning,
+                    format!("{change} since the baseline was recorded"),
+                    suggestion,
+                );
+            }
+        }
+
+        let third_party_count = report.count(PakClass::ThirdParty);
+        if third_party_count > 0 {
+            report.add(
+                Severity::Warning,
+                format!("{third_party_count} unknown third-party pak files found"),
+                "Mods may conflict with generated files, disable them to rule them out",
+            );
+        }
+
+        // print report
+        println!();
+        println!("{}", "Pak files".bold());
+        for class in [
+            PakClass::Official,
+            PakClass::Generated,
+            PakClass::Placeholder,
+            PakClass::Backup,
+            PakClass::Temp,
+            PakClass::ThirdParty,
+        ] {
+            println!("  {class}: {}", report.count(class));
+        }
+        for pak in report
+            .paks
+            .iter()
+            .filter(|pak| pak.class != PakClass::Official)
+        {
+            println!(
+                "    {} [{}] ({})",
+                pak.path
+                    .strip_prefix(game_dir)
+                    .unwrap_or(&pak.path)
+                    .display(),
+                pak.class,
+                human_bytes(pak.size)
+            );
+        }
+
+        println!();
+        if report.findings.is_empty() {
+            println!("{}", "No problems found.".green());
+            return Ok(());
+        }
+        println!("{}", "Problems".bold());
+        for finding in &report.findings {
+            match finding.severity {
+                Severity::Warning => println!("{}: {}", "Warning".yellow().bold(), finding.message),
+                Severity::Fatal => println!("{}: {}", "Error".red().bold(), finding.message),
+            }
+            println!("   Suggestion: {}", finding.suggestion);
+        }
+
+        Ok(())
+    }
+
+    fn unknown_hash_mode(&mut self) -> color_eyre::Result<()> {
+        let game_dir = self.input_game_dir()?;
+        let all_chunk_selections = self.scan_all_pak_files(&game_dir)?;
+        if all_chunk_selections.is_empty() {
+            bail!("No available pak files found.");
+        }
+
+        let output: String = Input::with_theme(&ColorfulTheme::default())
+            .show_default(true)
+            .default("unknown_hashes.json".to_string())
+            .with_prompt("Output report file path")
+            .interact_text()
+            .unwrap()
+            .trim_matches(|c| c == '\"' || c == '\'')
+            .to_string();
+
+        let filename_table = self.filename_table()?;
+
+        let bar = ProgressBar::new(all_chunk_selections.len() as u64);
+        bar.set_style(ProgressStyle::default_bar().template("{msg}\n{pos}/{len} {wide_bar}")?);
+
+        let mut report = UnknownHashReport::default();
+        for chunk_selection in &all_chunk_selections {
+            bar.set_message(chunk_selection.chunk_name.to_string());
+            let pak_entries = unknown_hash::scan_pak(&chunk_selection.full_path, filename_table)?;
+            if !pak_entries.entries.is_empty() {
+                report.paks.push(pak_entries);
+            }
+            bar.inc(1);
+        }
+        bar.finish_and_clear();
+
+        let file = fs::File::create(&output)?;
+        serde_json::to_writer_pretty(io::BufWriter::new(file), &report)?;
+
+        println!(
+            "Found {} unknown entries in {} pak files, report saved to {}",
+            report.total_unknown(),
+            report.paks.len(),
+            output
+        );
+
+        Ok(())
+    }
+
+    fn build_name_list_mode(&mut self) -> color_eyre::Result<()> {
+        let input: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Input raw path list file or directory")
+            .interact_text()
+            .unwrap()
+            .trim_matches(|c| c == '\"' || c == '\'')
+            .to_string();
+
+        let input_path = Path::new(&input);
+        let list_files = if input_path.is_dir() {
+            let mut files = Vec::new();
+            for entry in fs::read_dir(input_path)? {
+                let entry = entry?;
+                if entry.file_type()?.is_file() {
+                    files.push(entry.path());
+                }
+            }
+            files.sort();
+            files
+        } else if input_path.is_file() {
+            vec![input_path.to_path_buf()]
+        } else {
+            bail!("input path not exists.");
+        };
+
+        let mut paths = Vec::new();
+        for list_file in &list_files {
+            let data = fs::read(list_file)?;
+            let list_paths = if list_file.extension().is_some_and(|ext| ext == "zst") {
+                name_list::read_list(&data)?
+            } else {
+                name_list::parse_raw_list(&String::from_utf8_lossy(&data))
+            };
+            println!(
+                "Read {} paths from {}",
+                list_paths.len(),
+                list_file.display()
+            );
+            paths.extend(list_paths);
+        }
+
+        let include_embedded = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Include paths from the current file name list?")
+            .default(1)
+            .items(FALSE_TRUE_SELECTION)
+            .interact()
+            .unwrap();
+        if include_embedded == 1 {
+            paths.extend(name_list::read_list(&self.profile.name_list_data()?)?);
+        }
+
+        let mut paths = name_list::dedupe(paths);
+        println!("{} unique paths after normalization", paths.len());
+
+        let validate = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Drop paths not found in game pak files?")
+            .default(1)
+            .items(FALSE_TRUE_SELECTION)
+            .interact()
+            .unwrap();
+        if validate == 1 {
+            let game_dir = self.input_game_dir()?;
+            let chunk_selections = self.scan_all_pak_files(&game_dir)?;
+            if chunk_selections.is_empty() {
+                bail!("No available pak files found.");
+            }
+
+            println!("Reading pak entries...");
+            let hashes = collect_pak_hashes(&chunk_selections)?;
+            let count_before = paths.len();
+            paths = name_list::retain_existing(paths, &hashes);
+            println!(
+                "Dropped {} dead paths, {} paths left",
+                count_before - paths.len(),
+                paths.len()
+            );
+        }
+
+        for (a, b) in name_list::find_collisions(&paths) {
+            println!("Warning: hash collision between {a} and {b}");
+        }
+
+        let output: String = Input::with_theme(&ColorfulTheme::default())
+            .show_default(true)
+            .default(format!("{}.list.zst", self.profile.id))
+            .with_prompt("Output list file path")
+            .interact_text()
+            .unwrap()
+            .trim_matches(|c| c == '\"' || c == '\'')
+            .to_string();
+
+        let title = Path::new(&output)
+            .file_name()
+            .map(|name| {
+                name.to_string_lossy()
+                    .trim_end_matches(".list.zst")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        let description = format!(
+            "Generated by {} v{}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        );
+        let data = name_list::write_list(
+            &[
+                ("title", title.as_str()),
+                ("description", description.as_str()),
+            ],
+            &paths,
+        )?;
+        fs::write(&output, &data)?;
+
+        // make sure the output is readable by the pak library
+        FileNameTable::from_bytes(&data)?;
+        println!(
+            "Saved {} paths to {} ({})",
+            paths.len(),
+            output,
+            human_bytes(data.len() as u64)
+        );
+
+        Ok(())
+    }
+
+    /// Scan tool generated files in the discovered chunks, and backup and
+    /// temporary files in the search roots
+    fn scan_tool_files(
         &self,
-        dir: &Path,
+        game_dir: &Path,
         tool_generated_files: &mut Vec<(std::path::PathBuf, PakMetadata)>,
         backup_files: &mut Vec<std::path::PathBuf>,
+        temp_files: &mut Vec<std::path::PathBuf>,
         all_chunks: &mut Vec<ChunkName>,
     ) -> color_eyre::Result<()> {
-        let entries = fs::read_dir(dir)?;
-        for entry in entries {
-            let entry = entry?;
-            if !entry.file_type()?.is_file() {
+        let discovery = discovery::discover(game_dir, &self.profile.discovery_options())?;
+
+        for root in &discovery.roots {
+            for entry in fs::read_dir(root)? {
+                let entry = entry?;
+                if !entry.file_type()?.is_file() {
+                    continue;
+                }
+
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                // check backup files
+                if file_name.ends_with(".pak.backup") {
+                    backup_files.push(entry.path());
+                }
+                // check temporary files of replace mode
+                else if file_name.ends_with(".pak.temp") {
+                    temp_files.push(entry.path());
+                }
+            }
+        }
+
+        for chunk in discovery.chunks {
+            // check if the file is generated by this tool
+            if let Ok(Some(metadata)) = self.check_tool_generated_file(&chunk.full_path) {
+                tool_generated_files.push((chunk.full_path, metadata));
+            }
+            all_chunks.push(chunk.chunk_name);
+        }
+        Ok(())
+    }
+
+    /// Detect installed mods and warn about textures overlapping with the outputs
+    fn check_mods(
+        &self,
+        game_dir: &Path,
+        record: &Record,
+        chunk_selections: &[&ChunkSelection],
+    ) -> color_eyre::Result<Vec<DetectedMod>> {
+        println!("Checking installed mods...");
+        let loader_dlls = mods::find_loader_dlls(game_dir);
+
+        let mut detected_mods = Vec::new();
+        let discovery = discovery::discover(game_dir, &self.profile.discovery_options())?;
+        for chunk in &discovery.chunks {
+            let has_metadata = self.check_tool_generated_file(&chunk.full_path)?.is_some();
+            if mods::is_mod_pak(record, game_dir, &chunk.full_path, has_metadata) {
+                detected_mods.push(DetectedMod {
+                    source: ModSource::Pak(chunk.full_path.clone()),
+                    hashes: mods::read_pak_hashes(&chunk.full_path)?,
+                });
+            }
+        }
+        for skipped in &discovery.skipped {
+            if matches!(skipped.reason, SkipReason::InvalidChunkName(_))
+                && let Ok(hashes) = mods::read_pak_hashes(&skipped.path)
+            {
+                detected_mods.push(DetectedMod {
+                    source: ModSource::Pak(skipped.path.clone()),
+                    hashes,
+                });
+            }
+        }
+        if let Some(loose_files) = mods::scan_loose_files(game_dir)? {
+            detected_mods.push(loose_files);
+        }
+
+        if loader_dlls.is_empty() && detected_mods.is_empty() {
+            return Ok(detected_mods);
+        }
+
+        // textures that will be written to the outputs
+        let tex_index = self.tex_index()?;
+        let mut output_textures = HashSet::new();
+        for chunk_selection in chunk_selections {
+            let hashes = mods::read_pak_hashes(&chunk_selection.full_path)?;
+            output_textures.extend(hashes.into_iter().filter(|hash| tex_index.contains(*hash)));
+        }
+
+        for dll in &loader_dlls {
+            println!(
+                "{}: mod loader {dll} found, loose file mods will override generated textures",
+                "Warning".yellow().bold()
+            );
+        }
+        for detected_mod in &detected_mods {
+            let overlap = detected_mod
+                .hashes
+                .iter()
+                .filter(|hash| output_textures.contains(*hash))
+                .count();
+            if overlap == 0 {
+                println!("Found {}, no overlapping textures", detected_mod.source);
                 continue;
             }
+            let effect = match detected_mod.source {
+                ModSource::LooseFiles(_) => "the mod will override generated textures",
+                ModSource::Pak(_) => {
+                    "generated textures may override the mod, or be overridden by it, depending on load order"
+                }
+            };
+            println!(
+                "{}: {} contains {overlap} textures also in the outputs, {effect}",
+                "Warning".yellow().bold(),
+                detected_mod.source
+            );
+        }
 
-            let file_name = entry.file_name().to_string_lossy().to_string();
-            let file_path = entry.path();
+        let confirm = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Mods found, continue anyway?")
+            .default(0)
+            .items(FALSE_TRUE_SELECTION)
+            .interact()
+            .unwrap();
+        if confirm == 0 {
+            bail!("Cancelled, uninstall mods and try again.");
+        }
+
+        Ok(detected_mods)
+    }
 
-            // check backup files
-            if file_name.ends_with(".pak.backup") {
-                backup_files.push(file_path);
+    /// Entries of merged chunks overridden by other chunks in the same
+    /// directory, generated files are ignored unless they are inputs
+    fn superseded_hashes(
+        &self,
+        all_chunk_selections: &[ChunkSelection],
+        input_paths: &[&Path],
+    ) -> color_eyre::Result<HashSet<u64>> {
+        let output_dir = input_paths[0].parent();
+        let mut chunks = Vec::new();
+        let mut inputs = Vec::new();
+        for chunk in all_chunk_selections
+            .iter()
+            .filter(|cs| cs.full_path.parent() == output_dir)
+        {
+            let is_input = input_paths.contains(&chunk.full_path.as_path());
+            if !is_input && self.check_tool_generated_file(&chunk.full_path)?.is_some() {
                 continue;
             }
+            if is_input {
+                inputs.push(chunk.chunk_name.clone());
+            }
+            chunks.push((
+                chunk.chunk_name.clone(),
+                mods::read_pak_hashes(&chunk.full_path)?,
+            ));
+        }
+        Ok(mods::superseded_hashes(&chunks, &inputs))
+    }
 
-            // check pak files
-            if !file_name.ends_with(".pak") {
+    /// Entries of official patches loaded after the chunk in its series
+    fn later_patch_hashes(
+        &self,
+        game_dir: &Path,
+        record: &Record,
+        all_chunk_selections: &[ChunkSelection],
+        chunk_selection: &ChunkSelection,
+    ) -> color_eyre::Result<HashSet<u64>> {
+        let chunk_name = &chunk_selection.chunk_name;
+        let series = chunk_name.series();
+        let mut hashes = HashSet::new();
+        for later_patch in all_chunk_selections.iter().filter(|cs| {
+            cs.full_path.parent() == chunk_selection.full_path.parent()
+                && cs.chunk_name.series() == series
+                && cs.chunk_name.last_patch_id() > chunk_name.last_patch_id()
+        }) {
+            // mods and generated patches may be removed later, don't rely on them
+            if self.check_patch_kind(game_dir, record, &later_patch.full_path)?
+                != PatchKind::Official
+            {
                 continue;
             }
+            hashes.extend(mods::read_pak_hashes(&later_patch.full_path)?);
+        }
+        Ok(hashes)
+    }
+
+    /// Ask for the backup directory of replace mode, `None` to keep backups
+    /// next to the original files
+    fn select_backup_store(
+        &self,
+        game_dir: &Path,
+        record: &mut Record,
+    ) -> color_eyre::Result<Option<BackupStore>> {
+        // don't move away from a store that still holds backups
+        if let Some(store) = self.open_backup_store(game_dir, record)?
+            && !store.is_empty()
+        {
+            println!("Backups are stored in: {}", store.dir().display());
+            return Ok(Some(store));
+        }
 
-            // Check if it's a chunk or DLC file
-            let is_chunk = file_name.starts_with("re_chunk_");
-            let is_dlc = file_name.starts_with("re_dlc_");
+        let input: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Backup directory (leave empty to keep backups next to original files)")
+            .allow_empty(true)
+            .interact_text()?
+            .trim()
+            .trim_matches(|c| c == '\"' || c == '\'')
+            .to_string();
+        if input.is_empty() {
+            record.backup_dir = None;
+            return Ok(None);
+        }
 
-            if !is_chunk && !is_dlc {
-                continue;
+        record.backup_dir = Some(std::path::absolute(&input)?);
+        self.open_backup_store(game_dir, record)
+    }
+
+    /// Load the record of the game directory, a baseline of the install is
+    /// saved on the first run and after game updates.
+    ///
+    /// The user is asked which patch files are mods until the baseline is
+    /// confirmed, patch files of an unconfirmed baseline are not trusted.
+    fn load_record(&self, game_dir: &Path) -> color_eyre::Result<Record> {
+        let mut record = Record::load(game_dir)?;
+        let executable = game_dir.join(&self.profile.executable);
+        let outdated = record
+            .baseline
+            .as_ref()
+            .is_none_or(|baseline| baseline.is_outdated(&executable));
+        if outdated {
+            let discovery = discovery::discover(game_dir, &self.profile.discovery_options())?;
+            let mut files = Vec::new();
+            for chunk in &discovery.chunks {
+                files.push(BaselineFile::new(
+                    game_dir,
+                    &chunk.full_path,
+                    chunk.file_size,
+                    self.check_tool_generated_file(&chunk.full_path)?.is_some(),
+                ));
+            }
+            record.baseline = Some(Baseline::new(&executable, files));
+        }
+
+        let mut changed = outdated;
+        let baseline = record.baseline.as_mut().unwrap();
+        if !baseline.verified {
+            let patches = baseline
+                .unconfirmed_patches()
+                .into_iter()
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            let selection = if patches.is_empty() {
+                Some(Vec::new())
+            } else {
+                println!(
+                    "Taking a baseline of the install, patch files not selected here are treated as official."
+                );
+                MultiSelect::with_theme(&ColorfulTheme::default())
+                    .with_prompt(
+                        "Select patch files installed by mods (Space to select, Enter to confirm, Esc to ask again next time)",
+                    )
+                    .items(&patches)
+                    .interact_opt()?
+            };
+            if let Some(selection) = selection {
+                baseline.verify(selection.into_iter().map(|i| patches[i].clone()).collect());
+                changed = true;
             }
+        }
+        if changed {
+            record.save(game_dir)?;
+        }
+        Ok(record)
+    }
+
+    /// Open the backup store of the record, backups of each game are kept in
+    /// a sub directory named by the profile ID
+    fn open_backup_store(
+        &self,
+        game_dir: &Path,
+        record: &Record,
+    ) -> color_eyre::Result<Option<BackupStore>> {
+        match &record.backup_dir {
+            Some(backup_dir) => Ok(Some(BackupStore::open(
+                &backup_dir.join(&self.profile.id),
+                game_dir,
+            )?)),
+            None => Ok(None),
+        }
+    }
 
-            // collect chunk info
-            if let Ok(chunk_name) = ChunkName::try_from_str(&file_name) {
-                all_chunks.push(chunk_name.clone());
+    /// Find backups, temporary files and partial outputs that need attention
+    fn find_leftovers(
+        &self,
+        game_dir: &Path,
+        record: &Record,
+        backup_files: &[PathBuf],
+        temp_files: &[PathBuf],
+    ) -> color_eyre::Result<Vec<Leftover>> {
+        let mut leftovers = Vec::new();
+
+        for backup_path in backup_files {
+            let live_path = backup_path.with_extension("");
+            if !live_path.is_file() {
+                leftovers.push(Leftover::OrphanedBackup {
+                    backup_path: backup_path.clone(),
+                });
+            } else if self.check_tool_generated_file(&live_path)?.is_none() {
+                leftovers.push(Leftover::StaleBackup {
+                    backup_path: backup_path.clone(),
+                });
+            }
+        }
+        for temp_path in temp_files {
+            leftovers.push(Leftover::TempFile {
+                temp_path: temp_path.clone(),
+            });
+        }
+        for output in &record.outputs {
+            let path = output.full_path(game_dir);
+            if path.is_file() && !is_valid_pak(&path) {
+                leftovers.push(Leftover::PartialOutput { path });
             }
+        }
 
-            // check if the file is generated by this tool
-            if let Ok(Some(metadata)) = self.check_tool_generated_file(&file_path) {
-                tool_generated_files.push((file_path, metadata));
+        Ok(leftovers)
+    }
+
+    /// Patch files of a chunk series in a directory, sorted by patch ID
+    fn series_patch_files(
+        &self,
+        game_dir: &Path,
+        record: &Record,
+        chunk_selections: &[ChunkSelection],
+        dir: &Path,
+        series: &ChunkName,
+    ) -> color_eyre::Result<Vec<PatchFile>> {
+        let mut patches = Vec::new();
+        for chunk_selection in chunk_selections {
+            let chunk_name = &chunk_selection.chunk_name;
+            if !chunk_name.is_patch()
+                || chunk_name.series() != *series
+                || chunk_selection.full_path.parent() != Some(dir)
+            {
+                continue;
             }
+            patches.push(PatchFile {
+                chunk_name: chunk_name.clone(),
+                path: chunk_selection.full_path.clone(),
+                kind: self.check_patch_kind(game_dir, record, &chunk_selection.full_path)?,
+            });
         }
+        patches.sort_by_key(|patch| patch.chunk_name.last_patch_id());
+        Ok(patches)
+    }
+
+    /// Scan all patch files in the game directory and classify them.
+    ///
+    /// Outputs placed below other patches are treated as official, so they keep
+    /// their position.
+    fn scan_patch_files(
+        &self,
+        game_dir: &Path,
+        record: &Record,
+    ) -> color_eyre::Result<Vec<PatchFile>> {
+        println!("Scanning tool generated files...");
+        let discovery = discovery::discover(game_dir, &self.profile.discovery_options())?;
+
+        let mut patches = Vec::new();
+        for chunk in discovery.chunks {
+            if !chunk.chunk_name.is_patch() {
+                continue;
+            }
+            let kind = if record.is_placed(game_dir, &chunk.full_path) {
+                PatchKind::Official
+            } else {
+                self.check_patch_kind(game_dir, record, &chunk.full_path)?
+            };
+            patches.push(PatchFile {
+                chunk_name: chunk.chunk_name,
+                path: chunk.full_path,
+                kind,
+            });
+        }
+        Ok(patches)
+    }
+
+    /// Remove placeholders that are no longer needed and shift generated
+    /// patches down to close the gaps
+    fn compact_patches(&self, game_dir: &Path, record: &mut Record) -> color_eyre::Result<()> {
+        let patches = self.scan_patch_files(game_dir, record)?;
+        let (removals, renames) = patch_seq::plan_compaction(&patches);
+        if removals.is_empty() && renames.is_empty() {
+            return Ok(());
+        }
+
+        for path in &removals {
+            println!("Remove placeholder: {}", path.display());
+            fs::remove_file(path)?;
+            record.remove_output(game_dir, path);
+        }
+        for rename in &renames {
+            println!(
+                "Move patch: {} -> {}",
+                rename.from.display(),
+                rename.to.file_name().unwrap().to_string_lossy()
+            );
+        }
+        patch_seq::apply_renames(&renames)?;
+        record.rename_outputs(
+            game_dir,
+            renames
+                .iter()
+                .map(|rename| (rename.from.as_path(), rename.to.as_path())),
+        );
+
         Ok(())
     }
 
-    /// check if the file is generated by this tool, return metadata
+    /// Classify a patch file, files not generated by this tool are official
+    /// unless they were added after the install baseline
+    fn check_patch_kind(
+        &self,
+        game_dir: &Path,
+        record: &Record,
+        file_path: &Path,
+    ) -> color_eyre::Result<PatchKind> {
+        let file = match fs::File::open(file_path) {
+            Ok(file) => file,
+            Err(_) => return Ok(PatchKind::Official),
+        };
+
+        let mut reader = io::BufReader::new(file);
+        let pak_archive = match ree_pak_core::read::read_archive(&mut reader) {
+            Ok(archive) => archive,
+            Err(_) => return Ok(PatchKind::Official),
+        };
+
+        // placeholders only contain the metadata entry
+        let entry_count = pak_archive.entries().len();
+        let metadata = PakMetadata::from_pak_archive(reader, &pak_archive)?
+            .filter(|metadata| metadata.kind() == OutputKind::Decompressed);
+        let kind = match metadata {
+            None if mods::is_mod_pak(record, game_dir, file_path, false) => PatchKind::ThirdParty,
+            None => PatchKind::Official,
+            Some(_) if entry_count <= 1 => PatchKind::Placeholder,
+            Some(_) => PatchKind::Generated,
+        };
+        Ok(kind)
+    }
+
+    /// check if the file is generated by this tool, return metadata. Packed
+    /// paks carry metadata too, but they are treated like mods
     fn check_tool_generated_file(
         &self,
         file_path: &Path,
@@ -702,7 +2622,8 @@ I'm sure I've checked the list, press Enter to continue"#,
             Err(_) => return Ok(None),
         };
 
-        PakMetadata::from_pak_archive(reader, &pak_archive)
+        Ok(PakMetadata::from_pak_archive(reader, &pak_archive)?
+            .filter(|metadata| metadata.kind() == OutputKind::Decompressed))
     }
 
     /// create an empty patch file
@@ -724,11 +2645,110 @@ I'm sure I've checked the list, press Enter to continue"#,
     }
 }
 
-fn is_tex_file(hash: u64, file_name_table: &FileNameTable) -> bool {
-    let Some(file_name) = file_name_table.get_file_name(hash) else {
+/// Select where to insert the output in a patch series, returns the patch ID.
+///
+/// Official patches can't be moved, so only positions above them are offered.
+/// Generated and third-party patches above the position are moved up.
+fn select_patch_position(series_patches: &[PatchFile], top_id: u32) -> color_eyre::Result<u32> {
+    let max_official_id = series_patches
+        .iter()
+        .filter(|patch| patch.kind == PatchKind::Official)
+        .filter_map(|patch| patch.chunk_name.last_patch_id())
+        .max();
+    let movable_patches = series_patches
+        .iter()
+        .filter(|patch| patch.chunk_name.last_patch_id() > max_official_id)
+        .collect::<Vec<_>>();
+    if movable_patches.is_empty() {
+        return Ok(top_id);
+    }
+
+    let mut items = movable_patches
+        .iter()
+        .map(|patch| {
+            format!(
+                "Before {} [{}]",
+                patch.path.file_name().unwrap().to_string_lossy(),
+                patch.kind
+            )
+        })
+        .collect::<Vec<_>>();
+    items.push("After all patches".to_string());
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Insert the output")
+        .default(items.len() - 1)
+        .items(&items)
+        .interact()?;
+    Ok(movable_patches
+        .get(selection)
+        .and_then(|patch| patch.chunk_name.last_patch_id())
+        .unwrap_or(top_id))
+}
+
+/// Select compression of non-texture entries in full package outputs
+fn select_compression() -> color_eyre::Result<EntryCompression> {
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Compression of non-texture files (textures stay uncompressed)")
+        .default(0)
+        .items(["Store (no compression)", "Deflate", "Zstd (choose level)"])
+        .interact()
+        .unwrap();
+    let compression = match selection {
+        0 => EntryCompression::Store,
+        1 => EntryCompression::Deflate,
+        _ => {
+            let level: i32 = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Zstd level (1-22, higher is smaller but slower)")
+                .default(DEFAULT_ZSTD_LEVEL)
+                .validate_with(|level: &i32| {
+                    if (1..=22).contains(level) {
+                        Ok(())
+                    } else {
+                        Err("level must be between 1 and 22")
+                    }
+                })
+                .interact_text()?;
+            EntryCompression::Zstd { level }
+        }
+    };
+    Ok(compression)
+}
+
+/// Temporary path of an extra output part, moved into the patch sequence afterwards.
+///
+/// Named like other temporary files, so parts of an interrupted run are found
+/// as leftovers.
+fn part_path(output_path: &Path, part_index: usize) -> PathBuf {
+    let file_name = output_path.file_name().unwrap().to_string_lossy();
+    let name = file_name.strip_suffix(".temp").unwrap_or(&file_name);
+    let name = name.strip_suffix(".pak").unwrap_or(name);
+    output_path.with_file_name(format!("{name}.part{part_index}.pak.temp"))
+}
+
+/// Check if the file can be read as a pak archive
+fn is_valid_pak(path: &Path) -> bool {
+    let Ok(file) = fs::File::open(path) else {
         return false;
     };
-    file_name.to_string().unwrap().ends_with(".tex.241106027")
+    let mut reader = io::BufReader::new(file);
+    ree_pak_core::read::read_archive(&mut reader).is_ok()
+}
+
+/// Collect all entry hashes in the given pak files
+fn collect_pak_hashes(chunk_selections: &[ChunkSelection]) -> color_eyre::Result<HashSet<u64>> {
+    let mut hashes = HashSet::new();
+    for chunk_selection in chunk_selections {
+        hashes.extend(mods::read_pak_hashes(&chunk_selection.full_path)?);
+    }
+    Ok(hashes)
+}
+
+/// Decompress all mipmaps of a tex file
+fn decompress_tex(data: &[u8]) -> color_eyre::Result<Vec<u8>> {
+    let mut tex = Tex::from_reader(&mut io::Cursor::new(data))?;
+    tex.batch_decompress()?;
+    Ok(tex.as_bytes()?)
 }
 
 fn write_to_pak<W>(
@@ -737,11 +2757,12 @@ fn write_to_pak<W>(
     file_name: impl Utf16HashExt,
     data: &[u8],
     use_feature_clone: bool,
+    compression: EntryCompression,
 ) -> color_eyre::Result<usize>
 where
     W: io::Write + io::Seek,
 {
-    let mut file_options = FileOptions::default();
+    let mut file_options = compression.apply(FileOptions::default());
     if use_feature_clone {
         file_options = file_options.with_unk_attr(*entry.unk_attr())
     }
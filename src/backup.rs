@@ -0,0 +1,320 @@
+//! Backup store for original files replaced in replace mode.
+//!
+//! The store is a directory, usually on another drive, holding copies of the
+//! original files and a manifest with their paths relative to the game
+//! directory, sizes and SHA-256 hashes. Backups are verified before they are
+//! put back.
+
+use std::{
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::bail;
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::util::human_bytes;
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+const COPY_BUFFER_SIZE: usize = 1024 * 1024;
+const MANIFEST_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    version: u32,
+    #[serde(default)]
+    pub entries: Vec<BackupEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupEntry {
+    /// Path of the original file relative to the game directory
+    #[serde(alias = "original_path")]
+    pub relative_path: PathBuf,
+    /// Path of the backup file relative to the store directory
+    pub backup_file: PathBuf,
+    pub size: u64,
+    /// SHA-256 of the file content, hex encoded
+    pub sha256: String,
+}
+
+impl Default for BackupManifest {
+    fn default() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            entries: Vec::new(),
+        }
+    }
+}
+
+pub struct BackupStore {
+    dir: PathBuf,
+    manifest: BackupManifest,
+}
+
+impl BackupStore {
+    /// Open a store directory, the directory is created on first backup.
+    ///
+    /// Full paths in manifests of older versions are made relative to `game_dir`.
+    pub fn open(dir: &Path, game_dir: &Path) -> color_eyre::Result<Self> {
+        let manifest_path = dir.join(MANIFEST_FILE_NAME);
+        let manifest = if manifest_path.is_file() {
+            let mut manifest: BackupManifest =
+                serde_json::from_str(&fs::read_to_string(manifest_path)?)?;
+            // version 1 kept full paths
+            if manifest.version < 2 {
+                for entry in &mut manifest.entries {
+                    entry.relative_path =
+                        relative_path(game_dir, &entry.relative_path).to_path_buf();
+                }
+                manifest.version = MANIFEST_VERSION;
+            }
+            manifest
+        } else {
+            BackupManifest::default()
+        };
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            manifest,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.manifest.entries.is_empty()
+    }
+
+    pub fn entry(&self, relative_path: &Path) -> Option<&BackupEntry> {
+        self.manifest
+            .entries
+            .iter()
+            .find(|entry| entry.relative_path == relative_path)
+    }
+
+    /// Copy the original file into the store.
+    ///
+    /// `relative_path` is the path relative to the game directory, it keys
+    /// the backup and decides where it is placed in the store. Files outside
+    /// of the game directory are placed by their file name.
+    /// The original file is left untouched.
+    pub fn backup(&mut self, original_path: &Path, relative_path: &Path) -> color_eyre::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let size = fs::metadata(original_path)?.len();
+        let available = fs4::available_space(&self.dir)?;
+        if available < size {
+            bail!(
+                "Not enough space in backup directory {}: {} required, {} available",
+                self.dir.display(),
+                human_bytes(size),
+                human_bytes(available)
+            );
+        }
+
+        let backup_file = if relative_path.is_absolute() {
+            Path::new(relative_path.file_name().unwrap())
+        } else {
+            relative_path
+        };
+        let backup_path = self.dir.join(backup_file);
+        if let Some(parent) = backup_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let sha256 = copy_with_hash(original_path, &backup_path)?;
+
+        self.manifest
+            .entries
+            .retain(|entry| entry.relative_path != relative_path);
+        self.manifest.entries.push(BackupEntry {
+            relative_path: relative_path.to_path_buf(),
+            backup_file: backup_file.to_path_buf(),
+            size,
+            sha256,
+        });
+        self.save()
+    }
+
+    /// Verify the backup and copy it back to its path in the game directory,
+    /// replacing the current file. The backup is removed from the store
+    /// afterwards.
+    pub fn restore(&mut self, game_dir: &Path, relative_path: &Path) -> color_eyre::Result<()> {
+        let Some(entry) = self.entry(relative_path).cloned() else {
+            bail!("No backup found for {}", relative_path.display());
+        };
+        let original_path = game_dir.join(&entry.relative_path);
+
+        let backup_path = self.dir.join(&entry.backup_file);
+        let (size, sha256) = hash_file(&backup_path)?;
+        if size != entry.size || sha256 != entry.sha256 {
+            bail!(
+                "Backup file {} is corrupted, hash mismatch",
+                backup_path.display()
+            );
+        }
+
+        // copy next to the original first, so a failed copy keeps the current file
+        let mut temp_name = original_path.file_name().unwrap().to_os_string();
+        temp_name.push(".restore");
+        let temp_path = original_path.with_file_name(temp_name);
+        let copied_sha256 = copy_with_hash(&backup_path, &temp_path)?;
+        if copied_sha256 != entry.sha256 {
+            fs::remove_file(&temp_path)?;
+            bail!(
+                "Copy of {} is corrupted, hash mismatch",
+                backup_path.display()
+            );
+        }
+        fs::rename(&temp_path, &original_path)?;
+
+        fs::remove_file(&backup_path)?;
+        self.manifest
+            .entries
+            .retain(|entry| entry.relative_path != relative_path);
+        self.save()
+    }
+
+    fn save(&self) -> color_eyre::Result<()> {
+        let manifest_path = self.dir.join(MANIFEST_FILE_NAME);
+        if self.manifest.entries.is_empty() {
+            if manifest_path.is_file() {
+                fs::remove_file(manifest_path)?;
+            }
+            return Ok(());
+        }
+        fs::write(manifest_path, serde_json::to_string_pretty(&self.manifest)?)?;
+        Ok(())
+    }
+}
+
+/// Path of a file relative to the game directory, files outside of it keep
+/// their full path
+pub fn relative_path<'a>(game_dir: &Path, file_path: &'a Path) -> &'a Path {
+    file_path.strip_prefix(game_dir).unwrap_or(file_path)
+}
+
+/// Size and hex encoded SHA-256 of a file
+pub fn hash_file(path: &Path) -> color_eyre::Result<(u64, String)> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut file, &mut hasher)?;
+    Ok((size, hex_string(&hasher.finalize())))
+}
+
+/// Copy a file and return the hex encoded SHA-256 of its content
+fn copy_with_hash(from: &Path, to: &Path) -> color_eyre::Result<String> {
+    let mut reader = fs::File::open(from)?;
+    let mut writer = io::BufWriter::new(fs::File::create(to)?);
+    let mut hasher = Sha256::new();
+
+    let mut buf = vec![0; COPY_BUFFER_SIZE];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        writer.write_all(&buf[..n])?;
+    }
+    writer.into_inner()?.sync_all()?;
+
+    Ok(hex_string(&hasher.finalize()))
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_and_restore() {
+        let game_dir = tempfile::tempdir().unwrap();
+        let store_dir = tempfile::tempdir().unwrap();
+        let relative_path = Path::new("re_chunk_000.pak.sub_000.pak");
+        let original_path = game_dir.path().join(relative_path);
+        fs::write(&original_path, "original").unwrap();
+
+        let mut store = BackupStore::open(store_dir.path(), game_dir.path()).unwrap();
+        store.backup(&original_path, relative_path).unwrap();
+        fs::write(&original_path, "generated").unwrap();
+
+        // reopen from manifest
+        let mut store = BackupStore::open(store_dir.path(), game_dir.path()).unwrap();
+        assert_eq!(store.entry(relative_path).unwrap().size, 8);
+        store.restore(game_dir.path(), relative_path).unwrap();
+        assert_eq!(fs::read_to_string(&original_path).unwrap(), "original");
+        assert!(store.is_empty());
+        assert!(!store_dir.path().join(MANIFEST_FILE_NAME).exists());
+    }
+
+    #[test]
+    fn test_restore_corrupted() {
+        let game_dir = tempfile::tempdir().unwrap();
+        let store_dir = tempfile::tempdir().unwrap();
+        let original_path = game_dir.path().join("a.pak");
+        fs::write(&original_path, "original").unwrap();
+
+        let mut store = BackupStore::open(store_dir.path(), game_dir.path()).unwrap();
+        store.backup(&original_path, Path::new("a.pak")).unwrap();
+        fs::write(store_dir.path().join("a.pak"), "0riginal").unwrap();
+        fs::write(&original_path, "generated").unwrap();
+
+        assert!(store.restore(game_dir.path(), Path::new("a.pak")).is_err());
+        assert_eq!(fs::read_to_string(&original_path).unwrap(), "generated");
+    }
+
+    #[test]
+    fn test_restore_moved_game_dir() {
+        let game_dir = tempfile::tempdir().unwrap();
+        let moved_dir = tempfile::tempdir().unwrap();
+        let store_dir = tempfile::tempdir().unwrap();
+        let relative_path = Path::new("dlc/re_dlc_000.pak");
+        let original_path = game_dir.path().join(relative_path);
+        fs::create_dir_all(original_path.parent().unwrap()).unwrap();
+        fs::write(&original_path, "original").unwrap();
+
+        let mut store = BackupStore::open(store_dir.path(), game_dir.path()).unwrap();
+        store.backup(&original_path, relative_path).unwrap();
+
+        // the game is moved, e.g. to another Steam library
+        let moved_path = moved_dir.path().join(relative_path);
+        fs::create_dir_all(moved_path.parent().unwrap()).unwrap();
+        fs::write(&moved_path, "generated").unwrap();
+
+        let mut store = BackupStore::open(store_dir.path(), moved_dir.path()).unwrap();
+        store.restore(moved_dir.path(), relative_path).unwrap();
+        assert_eq!(fs::read_to_string(&moved_path).unwrap(), "original");
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_open_version_1_manifest() {
+        let game_dir = tempfile::tempdir().unwrap();
+        let store_dir = tempfile::tempdir().unwrap();
+        let original_path = game_dir.path().join("re_chunk_000.pak");
+        let manifest = serde_json::json!({
+            "version": 1,
+            "entries": [{
+                "original_path": original_path,
+                "backup_file": "re_chunk_000.pak",
+                "size": 8,
+                "sha256": "",
+            }],
+        });
+        fs::write(
+            store_dir.path().join(MANIFEST_FILE_NAME),
+            manifest.to_string(),
+        )
+        .unwrap();
+
+        let store = BackupStore::open(store_dir.path(), game_dir.path()).unwrap();
+        assert!(store.entry(Path::new("re_chunk_000.pak")).is_some());
+        assert!(store.entry(&original_path).is_none());
+    }
+}
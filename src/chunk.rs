@@ -5,9 +5,10 @@
 //! - Patch: re_chunk_XXX.pak.patch_XXX.pak
 //! - Sub: re_chunk_XXX.pak.sub_XXX.pak
 //! - Sub Patch: re_chunk_XXX.pak.sub_XXX.pak.patch_XXX.pak
-//! - DLC: re_dlc_stm_3308900.pak (and more)
-
-use color_eyre::eyre;
+//! - DLC: re_dlc_stm_3308900.pak
+//! - DLC Patch: re_dlc_stm_3308900.pak.patch_XXX.pak
+//! - DLC Sub: re_dlc_stm_3308900.pak.sub_XXX.pak
+//! - DLC Sub Patch: re_dlc_stm_3308900.pak.sub_XXX.pak.patch_XXX.pak
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ChunkComponent {
@@ -39,34 +40,32 @@ impl ChunkName {
     }
 
     /// Create a chunk name from a string
-    pub fn try_from_str(name: &str) -> color_eyre::Result<Self> {
+    pub fn try_from_str(name: &str) -> Result<Self, ChunkNameError> {
         let dot_parts = name.split('.').collect::<Vec<&str>>();
-        if dot_parts.len() < 2 || dot_parts.len() % 2 != 0 {
-            return Err(eyre::eyre!(
-                "Invalid chunk name with odd number of parts: {}",
-                name
-            ));
-        }
 
         // every 2 parts is a component
-        let part_pairs = dot_parts
-            .chunks_exact(2)
-            .map(|c| (c[0], c[1]))
-            .collect::<Vec<(&str, &str)>>();
-
-        // check if all parts have the correct extension
-        if !part_pairs.iter().all(|(_, ext)| *ext == "pak") {
-            return Err(eyre::eyre!(
-                "Invalid chunk name with invalid extension: {}",
-                name
-            ));
-        }
-
         let mut components = Vec::new();
         let mut has_sub = false;
 
-        for (part_name, _) in part_pairs.iter() {
-            let component = Self::parse_component(part_name)?;
+        for (position, pair) in dot_parts.chunks(2).enumerate() {
+            let part_name = pair[0];
+            match pair.get(1) {
+                Some(&"pak") => {}
+                Some(ext) => {
+                    return Err(ChunkNameError::BadExtension {
+                        position,
+                        part: format!("{part_name}.{ext}"),
+                    });
+                }
+                None => {
+                    return Err(ChunkNameError::MissingExtension {
+                        position,
+                        part: part_name.to_string(),
+                    });
+                }
+            }
+
+            let component = Self::parse_component(part_name, position)?;
             match component {
                 Component::Major(id) => {
                     components.push(ChunkComponent::Base(id));
@@ -91,7 +90,70 @@ impl ChunkName {
         Ok(Self { components })
     }
 
-    /// Get the major ID (base chunk ID)
+    /// Create a chunk name from a string, and reject illegal component sequences
+    pub fn try_from_str_strict(name: &str) -> Result<Self, ChunkNameError> {
+        let chunk_name = Self::try_from_str(name)?;
+        chunk_name.validate()?;
+        Ok(chunk_name)
+    }
+
+    /// Check the component sequence.
+    ///
+    /// Valid sequences are `Base|Dlc`, `Base|Dlc Patch`, `Base|Dlc Sub`
+    /// and `Base|Dlc Sub SubPatch`.
+    pub fn validate(&self) -> Result<(), ChunkNameError> {
+        let illegal_order = |position: usize, reason: &'static str| {
+            Err(ChunkNameError::IllegalOrder {
+                position,
+                part: self.components[position].to_string(),
+                reason,
+            })
+        };
+
+        for (position, component) in self.components.iter().enumerate() {
+            let prev = position.checked_sub(1).map(|i| &self.components[i]);
+            match (prev, component) {
+                (None, ChunkComponent::Base(_) | ChunkComponent::Dlc(_)) => {}
+                (None, _) => {
+                    return illegal_order(
+                        position,
+                        "chunk name must start with a base or DLC chunk",
+                    );
+                }
+                (Some(_), ChunkComponent::Base(_) | ChunkComponent::Dlc(_)) => {
+                    return illegal_order(
+                        position,
+                        "base or DLC chunk must be the first component",
+                    );
+                }
+                (
+                    Some(ChunkComponent::Base(_) | ChunkComponent::Dlc(_)),
+                    ChunkComponent::Patch(_),
+                )
+                | (
+                    Some(ChunkComponent::Base(_) | ChunkComponent::Dlc(_)),
+                    ChunkComponent::Sub(_),
+                )
+                | (Some(ChunkComponent::Sub(_)), ChunkComponent::SubPatch(_)) => {}
+                (Some(ChunkComponent::Sub(_)), ChunkComponent::Sub(_)) => {
+                    return illegal_order(position, "sub chunk after sub chunk");
+                }
+                (
+                    Some(ChunkComponent::Patch(_) | ChunkComponent::SubPatch(_)),
+                    ChunkComponent::Sub(_),
+                ) => {
+                    return illegal_order(position, "sub chunk after patch");
+                }
+                (Some(_), ChunkComponent::Patch(_) | ChunkComponent::SubPatch(_)) => {
+                    return illegal_order(position, "patch after patch");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the major ID (base chunk ID), None for DLC chunks
     pub fn major_id(&self) -> Option<u32> {
         self.components.iter().find_map(|c| match c {
             ChunkComponent::Base(id) => Some(*id),
@@ -99,6 +161,14 @@ impl ChunkName {
         })
     }
 
+    /// Get the DLC ID (stm_3308900 in re_dlc_stm_3308900.pak)
+    pub fn dlc_id(&self) -> Option<&str> {
+        self.components.iter().find_map(|c| match c {
+            ChunkComponent::Dlc(id) => Some(id.as_str()),
+            _ => None,
+        })
+    }
+
     /// Get the patch ID
     pub fn patch_id(&self) -> Option<u32> {
         self.components.iter().find_map(|c| match c {
@@ -123,55 +193,146 @@ impl ChunkName {
         })
     }
 
-    /// Add or replace a sub patch component with the given ID
-    pub fn set_sub_patch(&self, patch_id: u32) -> Self {
-        let mut new_components = self.components.clone();
+    /// Whether the chunk is a patch of a base, DLC or sub chunk
+    pub fn is_patch(&self) -> bool {
+        matches!(
+            self.components.last(),
+            Some(ChunkComponent::Patch(_) | ChunkComponent::SubPatch(_))
+        )
+    }
+
+    /// Get the ID of the trailing patch component, either patch or sub patch
+    pub fn last_patch_id(&self) -> Option<u32> {
+        match self.components.last() {
+            Some(ChunkComponent::Patch(id) | ChunkComponent::SubPatch(id)) => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// Get the chunk that this chunk patches, or itself if it's not a patch.
+    ///
+    /// Chunks with the same series share one patch sequence.
+    pub fn series(&self) -> Self {
+        let mut components = self.components.clone();
+        if self.is_patch() {
+            components.pop();
+        }
+        Self { components }
+    }
+
+    /// Get the patch chunk name with the given ID in the same series.
+    ///
+    /// Sub chunks get a sub patch, base and DLC chunks get a patch.
+    pub fn with_patch(&self, patch_id: u32) -> Self {
+        let mut series = self.series();
+        if series.sub_id().is_some() {
+            series.components.push(ChunkComponent::SubPatch(patch_id));
+        } else {
+            series.components.push(ChunkComponent::Patch(patch_id));
+        }
+        series
+    }
 
-        // Check if SubPatch already exists and replace it
-        if let Some(pos) = new_components
-            .iter()
-            .position(|c| matches!(c, ChunkComponent::SubPatch(_)))
+    fn parse_component(name: &str, position: usize) -> Result<Component, ChunkNameError> {
+        let parse_id = |id: &str| {
+            id.parse::<u32>()
+                .map_err(|error| ChunkNameError::InvalidNumber {
+                    position,
+                    part: name.to_string(),
+                    error,
+                })
+        };
+
+        if let Some(major_id) = name.strip_prefix("re_chunk_") {
+            Ok(Component::Major(parse_id(major_id)?))
+        } else if let Some(dlc_id) = name.strip_prefix("re_dlc_")
+            && !dlc_id.is_empty()
         {
-            new_components[pos] = ChunkComponent::SubPatch(patch_id);
+            Ok(Component::Dlc(dlc_id.to_string()))
+        } else if let Some(patch_id) = name.strip_prefix("patch_") {
+            Ok(Component::Patch(parse_id(patch_id)?))
+        } else if let Some(sub_id) = name.strip_prefix("sub_") {
+            Ok(Component::Sub(parse_id(sub_id)?))
         } else {
-            new_components.push(ChunkComponent::SubPatch(patch_id));
+            Err(ChunkNameError::UnknownComponent {
+                position,
+                part: name.to_string(),
+            })
         }
+    }
+}
 
-        Self {
-            components: new_components,
+/// Chunk name parse error.
+///
+/// `position` is the index of the offending component, counted from 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkNameError {
+    /// Component without `.pak` extension
+    MissingExtension { position: usize, part: String },
+    /// Component with an extension other than `.pak`
+    BadExtension { position: usize, part: String },
+    /// Component with unknown prefix
+    UnknownComponent { position: usize, part: String },
+    /// Component with an invalid ID number
+    InvalidNumber {
+        position: usize,
+        part: String,
+        error: std::num::ParseIntError,
+    },
+    /// Component in an illegal position
+    IllegalOrder {
+        position: usize,
+        part: String,
+        reason: &'static str,
+    },
+}
+
+impl std::fmt::Display for ChunkNameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkNameError::MissingExtension { position, part } => {
+                write!(f, "missing .pak extension at component {position} '{part}'")
+            }
+            ChunkNameError::BadExtension { position, part } => {
+                write!(f, "bad extension at component {position} '{part}'")
+            }
+            ChunkNameError::UnknownComponent { position, part } => {
+                write!(f, "unknown component {position} '{part}'")
+            }
+            ChunkNameError::InvalidNumber {
+                position,
+                part,
+                error,
+            } => write!(
+                f,
+                "invalid number at component {position} '{part}': {error}"
+            ),
+            ChunkNameError::IllegalOrder {
+                position,
+                part,
+                reason,
+            } => write!(f, "illegal component {position} '{part}': {reason}"),
         }
     }
+}
 
-    fn parse_component(name: &str) -> color_eyre::Result<Component> {
-        if name.starts_with("re_chunk_") {
-            let major_id = name
-                .strip_prefix("re_chunk_")
-                .unwrap()
-                .parse::<u32>()
-                .map_err(|e| eyre::eyre!("Chunk name with invalid major ID: {}", e))?;
-            Ok(Component::Major(major_id))
-        } else if name.starts_with("re_dlc_") {
-            let dlc_id = name.strip_prefix("re_dlc_").unwrap().to_string();
-            Ok(Component::Dlc(dlc_id))
-        } else if name.starts_with("patch_") {
-            let patch_id = name
-                .strip_prefix("patch_")
-                .unwrap()
-                .parse::<u32>()
-                .map_err(|e| eyre::eyre!("Chunk name with invalid patch ID: {}", e))?;
-            Ok(Component::Patch(patch_id))
-        } else if name.starts_with("sub_") {
-            let sub_id = name
-                .strip_prefix("sub_")
-                .unwrap()
-                .parse::<u32>()
-                .map_err(|e| eyre::eyre!("Chunk name with invalid sub ID: {}", e))?;
-            Ok(Component::Sub(sub_id))
-        } else {
-            Err(eyre::eyre!(
-                "Invalid chunk name with invalid component: {}",
-                name
-            ))
+impl std::error::Error for ChunkNameError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ChunkNameError::InvalidNumber { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ChunkComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkComponent::Base(id) => write!(f, "re_chunk_{:03}.pak", id),
+            ChunkComponent::Dlc(id) => write!(f, "re_dlc_{}.pak", id),
+            ChunkComponent::Patch(id) => write!(f, "patch_{:03}.pak", id),
+            ChunkComponent::Sub(id) => write!(f, "sub_{:03}.pak", id),
+            ChunkComponent::SubPatch(id) => write!(f, "patch_{:03}.pak", id),
         }
     }
 }
@@ -182,13 +343,7 @@ impl std::fmt::Display for ChunkName {
             if i > 0 {
                 write!(f, ".")?;
             }
-            match component {
-                ChunkComponent::Base(id) => write!(f, "re_chunk_{:03}.pak", id)?,
-                ChunkComponent::Dlc(id) => write!(f, "re_dlc_{}.pak", id)?,
-                ChunkComponent::Patch(id) => write!(f, "patch_{:03}.pak", id)?,
-                ChunkComponent::Sub(id) => write!(f, "sub_{:03}.pak", id)?,
-                ChunkComponent::SubPatch(id) => write!(f, "patch_{:03}.pak", id)?,
-            }
+            write!(f, "{component}")?;
         }
         Ok(())
     }
@@ -296,19 +451,118 @@ mod tests {
         // Test DLC chunk helper methods
         let dlc = ChunkName::try_from_str("re_dlc_stm_3308900.pak").unwrap();
         assert_eq!(dlc.major_id(), None);
+        assert_eq!(dlc.dlc_id(), Some("stm_3308900"));
+
+        let dlc_sub_patch =
+            ChunkName::try_from_str("re_dlc_stm_3308900.pak.sub_000.pak.patch_002.pak").unwrap();
+        assert_eq!(dlc_sub_patch.dlc_id(), Some("stm_3308900"));
+        assert_eq!(dlc_sub_patch.sub_id(), Some(0));
+        assert_eq!(dlc_sub_patch.sub_patch_id(), Some(2));
+        assert_eq!(dlc_sub_patch.last_patch_id(), Some(2));
+        assert!(dlc_sub_patch.is_patch());
     }
 
     #[test]
-    fn test_set_sub_patch() {
-        let base = ChunkName::try_from_str("re_chunk_000.pak.sub_001.pak").unwrap();
-        let with_patch = base.set_sub_patch(99);
+    fn test_series_and_with_patch() {
+        let sub = ChunkName::try_from_str("re_chunk_000.pak.sub_001.pak").unwrap();
+        let sub_patch = sub.with_patch(3);
+        assert_eq!(
+            sub_patch.to_string(),
+            "re_chunk_000.pak.sub_001.pak.patch_003.pak"
+        );
+        assert_eq!(sub_patch.series(), sub);
+        assert_eq!(sub_patch.with_patch(4).sub_patch_id(), Some(4));
+
+        let dlc = ChunkName::try_from_str("re_dlc_stm_3308900.pak").unwrap();
+        let dlc_patch = dlc.with_patch(1);
+        assert_eq!(
+            dlc_patch.to_string(),
+            "re_dlc_stm_3308900.pak.patch_001.pak"
+        );
+        assert_eq!(dlc_patch.patch_id(), Some(1));
+        assert_eq!(dlc_patch.series(), dlc);
+        // round trip keeps the same structure
+        assert_eq!(
+            ChunkName::try_from_str(&dlc_patch.to_string()).unwrap(),
+            dlc_patch
+        );
+
+        let dlc_sub = ChunkName::try_from_str("re_dlc_stm_3308900.pak.sub_000.pak").unwrap();
+        assert_eq!(
+            dlc_sub.with_patch(1).to_string(),
+            "re_dlc_stm_3308900.pak.sub_000.pak.patch_001.pak"
+        );
+        // different DLCs are different series
+        let other_dlc = ChunkName::try_from_str("re_dlc_stm_3308901.pak.sub_000.pak").unwrap();
+        assert_ne!(dlc_sub.series(), other_dlc.series());
+    }
 
-        assert_eq!(with_patch.major_id(), Some(0));
-        assert_eq!(with_patch.sub_id(), Some(1));
-        assert_eq!(with_patch.sub_patch_id(), Some(99));
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            ChunkName::try_from_str("re_chunk_000"),
+            Err(ChunkNameError::MissingExtension {
+                position: 0,
+                part: "re_chunk_000".to_string()
+            })
+        );
+        assert_eq!(
+            ChunkName::try_from_str("re_chunk_000.pak.sub_000.bak"),
+            Err(ChunkNameError::BadExtension {
+                position: 1,
+                part: "sub_000.bak".to_string()
+            })
+        );
         assert_eq!(
-            with_patch.to_string(),
-            "re_chunk_000.pak.sub_001.pak.patch_099.pak"
+            ChunkName::try_from_str("re_chunk_000.pak.foo_000.pak"),
+            Err(ChunkNameError::UnknownComponent {
+                position: 1,
+                part: "foo_000".to_string()
+            })
         );
+        assert!(matches!(
+            ChunkName::try_from_str("re_chunk_000.pak.patch_abc.pak"),
+            Err(ChunkNameError::InvalidNumber { position: 1, .. })
+        ));
+        assert!(matches!(
+            ChunkName::try_from_str("re_dlc_.pak"),
+            Err(ChunkNameError::UnknownComponent { position: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_strict_validation() {
+        for name in [
+            "re_chunk_000.pak",
+            "re_chunk_000.pak.patch_001.pak",
+            "re_chunk_000.pak.sub_000.pak",
+            "re_chunk_000.pak.sub_000.pak.patch_001.pak",
+            "re_dlc_stm_3308900.pak.sub_000.pak.patch_001.pak",
+        ] {
+            assert!(ChunkName::try_from_str_strict(name).is_ok(), "{name}");
+        }
+
+        let illegal = [
+            ("patch_001.pak.re_chunk_000.pak", 0),
+            ("sub_000.pak", 0),
+            ("re_chunk_000.pak.re_chunk_001.pak", 1),
+            ("re_chunk_000.pak.sub_000.pak.sub_001.pak", 2),
+            ("re_chunk_000.pak.patch_001.pak.sub_000.pak", 2),
+            ("re_chunk_000.pak.patch_001.pak.patch_002.pak", 2),
+            (
+                "re_chunk_000.pak.sub_000.pak.patch_001.pak.patch_002.pak",
+                3,
+            ),
+        ];
+        for (name, expected_position) in illegal {
+            // accepted by the lenient parser
+            assert!(ChunkName::try_from_str(name).is_ok(), "{name}");
+            match ChunkName::try_from_str_strict(name) {
+                Err(ChunkNameError::IllegalOrder { position, .. }) => {
+                    assert_eq!(position, expected_position, "{name}")
+                }
+                other => panic!("{name}: unexpected result {other:?}"),
+            }
+        }
     }
 }
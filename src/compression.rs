@@ -0,0 +1,58 @@
+//! Compression of non-texture entries in generated paks.
+//!
+//! Textures are always stored uncompressed, that's the point of this tool.
+//! Other entries in full package outputs can be compressed to save space.
+
+use ree_pak_core::{pak::CompressionType, write::FileOptions};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum EntryCompression {
+    /// No compression
+    #[default]
+    Store,
+    Deflate,
+    Zstd {
+        level: i32,
+    },
+}
+
+impl EntryCompression {
+    pub fn apply(self, file_options: FileOptions) -> FileOptions {
+        match self {
+            EntryCompression::Store => file_options,
+            EntryCompression::Deflate => {
+                file_options.with_compression_type(CompressionType::DEFLATE)
+            }
+            EntryCompression::Zstd { level } => file_options
+                .with_compression_type(CompressionType::ZSTD)
+                .with_compression_level(level),
+        }
+    }
+}
+
+impl std::fmt::Display for EntryCompression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EntryCompression::Store => write!(f, "store"),
+            EntryCompression::Deflate => write!(f, "deflate"),
+            EntryCompression::Zstd { level } => write!(f, "zstd level {level}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde() {
+        let json = serde_json::to_string(&EntryCompression::Zstd { level: 3 }).unwrap();
+        assert_eq!(json, r#"{"method":"zstd","level":3}"#);
+        let compression: EntryCompression = serde_json::from_str(r#"{"method":"store"}"#).unwrap();
+        assert_eq!(compression, EntryCompression::Store);
+    }
+}
@@ -0,0 +1,343 @@
+//! Comparison of pak contents between two paks or two sets of chunks.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+use ree_pak_core::{filename::FileNameTable, pak::PakEntry, read::archive::PakArchiveReader};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Entries of one or more pak files, later paks override earlier ones like in game.
+#[derive(Debug, Default)]
+pub struct PakSet {
+    paks: Vec<PathBuf>,
+    entries: HashMap<u64, (usize, PakEntry)>,
+}
+
+impl PakSet {
+    /// Read pak files, must be given in load order
+    pub fn load(paths: &[PathBuf]) -> color_eyre::Result<Self> {
+        let mut set = PakSet::default();
+        for path in paths {
+            let index = set.paks.len();
+            set.paks.push(path.clone());
+
+            let file = fs::File::open(path)?;
+            let mut reader = io::BufReader::new(file);
+            let pak_archive = ree_pak_core::read::read_archive(&mut reader)?;
+            for entry in pak_archive.entries() {
+                set.entries.insert(entry.hash(), (index, entry.clone()));
+            }
+        }
+        Ok(set)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn size(&self, hash: u64) -> Option<u64> {
+        self.entries
+            .get(&hash)
+            .map(|(_, entry)| entry.decompressed_size())
+    }
+
+    /// SHA-256 of the decompressed content of the given entries
+    fn content_hashes(&self, hashes: &HashSet<u64>) -> color_eyre::Result<HashMap<u64, Vec<u8>>> {
+        let mut result = HashMap::new();
+        for (index, pak_path) in self.paks.iter().enumerate() {
+            let entries = self
+                .entries
+                .iter()
+                .filter(|(hash, (i, _))| *i == index && hashes.contains(hash))
+                .collect::<Vec<_>>();
+            if entries.is_empty() {
+                continue;
+            }
+
+            let file = fs::File::open(pak_path)?;
+            let mut reader = io::BufReader::new(file);
+            let pak_archive = ree_pak_core::read::read_archive(&mut reader)?;
+            let mut archive_reader = PakArchiveReader::new(reader, &pak_archive);
+            for (hash, (_, entry)) in entries {
+                let mut entry_reader = archive_reader.owned_entry_reader(entry.clone())?;
+                let mut hasher = Sha256::new();
+                io::copy(&mut entry_reader, &mut hasher)?;
+                result.insert(*hash, hasher.finalize().to_vec());
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl std::fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChangeKind::Added => write!(f, "added"),
+            ChangeKind::Removed => write!(f, "removed"),
+            ChangeKind::Changed => write!(f, "changed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffEntry {
+    /// Entry hash in hex, as used by file name lists
+    pub hash: String,
+    /// File name from the file name table, None if unknown
+    pub name: Option<String>,
+    pub kind: ChangeKind,
+    pub old_size: Option<u64>,
+    pub new_size: Option<u64>,
+}
+
+impl DiffEntry {
+    pub fn is_texture(&self) -> bool {
+        self.name
+            .as_deref()
+            .is_some_and(|name| name.to_lowercase().contains(".tex."))
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DiffReport {
+    pub old_paks: Vec<String>,
+    pub new_paks: Vec<String>,
+    pub entries: Vec<DiffEntry>,
+}
+
+impl DiffReport {
+    pub fn count(&self, kind: ChangeKind) -> usize {
+        self.entries.iter().filter(|e| e.kind == kind).count()
+    }
+
+    pub fn texture_count(&self, kind: ChangeKind) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| e.kind == kind && e.is_texture())
+            .count()
+    }
+
+    pub fn write_json(&self, path: &Path) -> color_eyre::Result<()> {
+        let file = fs::File::create(path)?;
+        serde_json::to_writer_pretty(io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    pub fn write_csv(&self, path: &Path) -> color_eyre::Result<()> {
+        let mut writer = io::BufWriter::new(fs::File::create(path)?);
+        self.write_csv_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn write_csv_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "hash,name,kind,old_size,new_size")?;
+        for entry in &self.entries {
+            let size = |size: Option<u64>| size.map(|s| s.to_string()).unwrap_or_default();
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                entry.hash,
+                csv_field(entry.name.as_deref().unwrap_or_default()),
+                entry.kind,
+                size(entry.old_size),
+                size(entry.new_size)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare two pak sets, and name the changed entries.
+pub fn diff(
+    old: &PakSet,
+    new: &PakSet,
+    filename_table: &FileNameTable,
+) -> color_eyre::Result<DiffReport> {
+    let mut entries = diff_entries(old, new)?
+        .into_iter()
+        .map(|(hash, kind)| DiffEntry {
+            hash: format!("{hash:016x}"),
+            name: filename_table
+                .get_file_name(hash)
+                .map(|name| name.get_name().to_string()),
+            kind,
+            old_size: old.size(hash),
+            new_size: new.size(hash),
+        })
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| (a.kind as u8, &a.name, &a.hash).cmp(&(b.kind as u8, &b.name, &b.hash)));
+
+    let pak_names = |set: &PakSet| {
+        set.paks
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect()
+    };
+    Ok(DiffReport {
+        old_paks: pak_names(old),
+        new_paks: pak_names(new),
+        entries,
+    })
+}
+
+/// Changed entry hashes of two pak sets.
+///
+/// Entries present on both sides are changed if their sizes differ, or their
+/// content hashes differ when sizes are equal.
+fn diff_entries(old: &PakSet, new: &PakSet) -> color_eyre::Result<Vec<(u64, ChangeKind)>> {
+    let mut changes: Vec<(u64, ChangeKind)> = Vec::new();
+    let mut same_size = HashSet::new();
+    for hash in new.entries.keys() {
+        match old.size(*hash) {
+            None => changes.push((*hash, ChangeKind::Added)),
+            Some(old_size) if Some(old_size) != new.size(*hash) => {
+                changes.push((*hash, ChangeKind::Changed))
+            }
+            Some(_) => {
+                same_size.insert(*hash);
+            }
+        }
+    }
+    for hash in old.entries.keys() {
+        if !new.entries.contains_key(hash) {
+            changes.push((*hash, ChangeKind::Removed));
+        }
+    }
+
+    let old_hashes = old.content_hashes(&same_size)?;
+    let new_hashes = new.content_hashes(&same_size)?;
+    for hash in same_size {
+        if old_hashes.get(&hash) != new_hashes.get(&hash) {
+            changes.push((hash, ChangeKind::Changed));
+        }
+    }
+    Ok(changes)
+}
+
+/// Quote a CSV field if needed
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ree_pak_core::{
+        utf16_hash::Utf16HashExt,
+        write::{FileOptions, PakWriter},
+    };
+
+    use super::*;
+
+    fn write_pak(path: &Path, files: &[(&str, &[u8])]) {
+        let file = fs::File::create(path).unwrap();
+        let mut pak_writer = PakWriter::new(file, files.len() as u64);
+        for (name, data) in files {
+            pak_writer
+                .start_file(*name, FileOptions::default())
+                .unwrap();
+            pak_writer.write_all(data).unwrap();
+        }
+        pak_writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_diff_entries() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let old_path = temp_dir.path().join("old.pak");
+        let base_path = temp_dir.path().join("base.pak");
+        let patch_path = temp_dir.path().join("patch.pak");
+        write_pak(
+            &old_path,
+            &[
+                ("natives/same.txt", b"same"),
+                ("natives/size.txt", b"12"),
+                ("natives/content.txt", b"ab"),
+                ("natives/removed.txt", b"gone"),
+            ],
+        );
+        write_pak(
+            &base_path,
+            &[
+                ("natives/same.txt", b"same"),
+                ("natives/size.txt", b"12"),
+                ("natives/content.txt", b"ab"),
+            ],
+        );
+        // overrides the base pak
+        write_pak(
+            &patch_path,
+            &[
+                ("natives/size.txt", b"123"),
+                ("natives/content.txt", b"cd"),
+                ("natives/added.txt", b"new"),
+            ],
+        );
+
+        let old = PakSet::load(&[old_path]).unwrap();
+        let new = PakSet::load(&[base_path, patch_path]).unwrap();
+        assert_eq!(new.len(), 4);
+        assert_eq!(new.size("natives/size.txt".hash_mixed()), Some(3));
+
+        let mut changes = diff_entries(&old, &new).unwrap();
+        changes.sort_by_key(|(hash, _)| *hash);
+        let mut expected = vec![
+            ("natives/size.txt".hash_mixed(), ChangeKind::Changed),
+            ("natives/content.txt".hash_mixed(), ChangeKind::Changed),
+            ("natives/added.txt".hash_mixed(), ChangeKind::Added),
+            ("natives/removed.txt".hash_mixed(), ChangeKind::Removed),
+        ];
+        expected.sort_by_key(|(hash, _)| *hash);
+        assert_eq!(changes, expected);
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let report = DiffReport {
+            entries: vec![
+                DiffEntry {
+                    hash: "0000000000000001".to_string(),
+                    name: Some("natives/STM/a,b.tex.241106027".to_string()),
+                    kind: ChangeKind::Changed,
+                    old_size: Some(10),
+                    new_size: Some(20),
+                },
+                DiffEntry {
+                    hash: "0000000000000002".to_string(),
+                    name: None,
+                    kind: ChangeKind::Added,
+                    old_size: None,
+                    new_size: Some(5),
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(report.texture_count(ChangeKind::Changed), 1);
+
+        let mut buf = Vec::new();
+        report.write_csv_to(&mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "hash,name,kind,old_size,new_size\n\
+             0000000000000001,\"natives/STM/a,b.tex.241106027\",changed,10,20\n\
+             0000000000000002,,added,,5\n"
+        );
+    }
+}
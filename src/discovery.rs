@@ -0,0 +1,386 @@
+//! Chunk file discovery in the game directory.
+
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+use serde::Deserialize;
+
+use crate::chunk::{ChunkName, ChunkNameError};
+
+/// Chunk discovery options, configurable in game profiles.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DiscoveryOptions {
+    /// Search roots relative to the game directory.
+    /// Empty for the game directory and the profile's DLC directory.
+    pub roots: Vec<PathBuf>,
+    /// Max recursion depth into sub directories, 0 for the root only
+    pub max_depth: usize,
+    /// Glob patterns of relative paths to include, empty to include all
+    pub include: Vec<String>,
+    /// Glob patterns of relative paths to exclude
+    pub exclude: Vec<String>,
+    /// Follow symbolic links to files and directories
+    pub follow_symlinks: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiscoveredChunk {
+    pub chunk_name: ChunkName,
+    pub file_size: u64,
+    pub full_path: PathBuf,
+    /// Index of the search root the chunk was found in
+    pub root_index: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Not matched by include patterns, or matched by exclude patterns
+    Excluded,
+    /// File name is not a valid chunk name
+    InvalidChunkName(ChunkNameError),
+    /// Symbolic link while following is disabled
+    Symlink,
+    /// Directory already visited, usually caused by symbolic link loops
+    AlreadyVisited,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::Excluded => write!(f, "excluded by pattern"),
+            SkipReason::InvalidChunkName(e) => write!(f, "invalid chunk name: {e}"),
+            SkipReason::Symlink => write!(f, "symbolic link"),
+            SkipReason::AlreadyVisited => write!(f, "already visited"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, Default)]
+pub struct Discovery {
+    /// Search roots that exist, as full paths
+    pub roots: Vec<PathBuf>,
+    /// Chunks sorted by chunk name
+    pub chunks: Vec<DiscoveredChunk>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+impl Discovery {
+    /// Summary of skipped files, grouped by reason kind
+    pub fn skipped_summary(&self) -> Option<String> {
+        if self.skipped.is_empty() {
+            return None;
+        }
+
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for skipped in &self.skipped {
+            let kind = match skipped.reason {
+                SkipReason::Excluded => "excluded",
+                SkipReason::InvalidChunkName(_) => "invalid name",
+                SkipReason::Symlink => "symlink",
+                SkipReason::AlreadyVisited => "already visited",
+            };
+            match counts.iter_mut().find(|(k, _)| *k == kind) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((kind, 1)),
+            }
+        }
+
+        let details = counts
+            .iter()
+            .map(|(kind, count)| format!("{count} {kind}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Some(format!("Skipped {} files ({details})", self.skipped.len()))
+    }
+}
+
+/// Discover chunk files under the search roots.
+pub fn discover(game_dir: &Path, options: &DiscoveryOptions) -> color_eyre::Result<Discovery> {
+    let mut discovery = Discovery::default();
+    let mut visited_dirs = Vec::new();
+
+    for root in &options.roots {
+        let root_dir = if root == Path::new(".") {
+            game_dir.to_path_buf()
+        } else {
+            game_dir.join(root)
+        };
+        if !root_dir.is_dir() {
+            continue;
+        }
+        let root_index = discovery.roots.len();
+        discovery.roots.push(root_dir.clone());
+
+        let mut ctx = WalkContext {
+            game_dir,
+            options,
+            root_index,
+            visited_dirs: &mut visited_dirs,
+            discovery: &mut discovery,
+        };
+        ctx.walk(&root_dir, 0)?;
+    }
+
+    discovery
+        .chunks
+        .sort_by(|a, b| a.chunk_name.cmp(&b.chunk_name));
+    Ok(discovery)
+}
+
+struct WalkContext<'a> {
+    game_dir: &'a Path,
+    options: &'a DiscoveryOptions,
+    root_index: usize,
+    visited_dirs: &'a mut Vec<PathBuf>,
+    discovery: &'a mut Discovery,
+}
+
+impl WalkContext<'_> {
+    fn walk(&mut self, dir: &Path, depth: usize) -> color_eyre::Result<()> {
+        // roots may overlap, e.g. "." with depth and "dlc"
+        let canonical_dir = fs::canonicalize(dir)?;
+        if self.visited_dirs.contains(&canonical_dir) {
+            if depth > 0 {
+                self.skip(dir, SkipReason::AlreadyVisited);
+            }
+            return Ok(());
+        }
+        self.visited_dirs.push(canonical_dir);
+
+        let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let path = entry.path();
+            let mut file_type = entry.file_type()?;
+            if file_type.is_symlink() {
+                if !self.options.follow_symlinks {
+                    if path.to_string_lossy().ends_with(".pak") {
+                        self.skip(&path, SkipReason::Symlink);
+                    }
+                    continue;
+                }
+                match fs::metadata(&path) {
+                    Ok(metadata) => file_type = metadata.file_type(),
+                    // broken link
+                    Err(_) => continue,
+                }
+            }
+
+            if file_type.is_dir() {
+                if depth >= self.options.max_depth {
+                    continue;
+                }
+                if self.is_dir_excluded(&path) {
+                    self.skip(&path, SkipReason::Excluded);
+                    continue;
+                }
+                self.walk(&path, depth + 1)?;
+            } else if file_type.is_file() {
+                self.visit_file(&path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn visit_file(&mut self, path: &Path) -> color_eyre::Result<()> {
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        if !file_name.ends_with(".pak") {
+            return Ok(());
+        }
+        if !self.is_included(path) || self.is_excluded(path) {
+            self.skip(path, SkipReason::Excluded);
+            return Ok(());
+        }
+
+        let chunk_name = match ChunkName::try_from_str_strict(&file_name) {
+            Ok(chunk_name) => chunk_name,
+            Err(e) => {
+                self.skip(path, SkipReason::InvalidChunkName(e));
+                return Ok(());
+            }
+        };
+
+        self.discovery.chunks.push(DiscoveredChunk {
+            chunk_name,
+            file_size: fs::metadata(path)?.len(),
+            full_path: path.to_path_buf(),
+            root_index: self.root_index,
+        });
+        Ok(())
+    }
+
+    fn skip(&mut self, path: &Path, reason: SkipReason) {
+        self.discovery.skipped.push(SkippedFile {
+            path: path.to_path_buf(),
+            reason,
+        });
+    }
+
+    fn relative_path(&self, path: &Path) -> String {
+        path.strip_prefix(self.game_dir)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/")
+            .trim_start_matches("./")
+            .to_string()
+    }
+
+    fn is_included(&self, path: &Path) -> bool {
+        let relative_path = self.relative_path(path);
+        self.options.include.is_empty()
+            || self
+                .options
+                .include
+                .iter()
+                .any(|pattern| glob_match(pattern, &relative_path))
+    }
+
+    fn is_excluded(&self, path: &Path) -> bool {
+        let relative_path = self.relative_path(path);
+        self.options
+            .exclude
+            .iter()
+            .any(|pattern| glob_match(pattern, &relative_path))
+    }
+
+    /// Directories also match patterns like `mods/**`
+    fn is_dir_excluded(&self, path: &Path) -> bool {
+        let relative_path = format!("{}/", self.relative_path(path));
+        self.is_excluded(path)
+            || self
+                .options
+                .exclude
+                .iter()
+                .any(|pattern| glob_match(pattern, &relative_path))
+    }
+}
+
+/// Match text against a glob pattern.
+///
+/// Supports `*` (any characters except `/`), `**` (any characters) and `?`
+/// (one character except `/`). Matching is case-insensitive.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+    glob_match_chars(&pattern, &text)
+}
+
+fn glob_match_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            // `**/` also matches zero directories
+            let rest = &pattern[2..];
+            if rest.first() == Some(&'/') && glob_match_chars(&rest[1..], text) {
+                return true;
+            }
+            (0..=text.len()).any(|i| glob_match_chars(rest, &text[i..]))
+        }
+        Some('*') => {
+            let rest = &pattern[1..];
+            for i in 0..=text.len() {
+                if glob_match_chars(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => {
+            matches!(text.first(), Some(c) if *c != '/')
+                && glob_match_chars(&pattern[1..], &text[1..])
+        }
+        Some(c) => text.first() == Some(c) && glob_match_chars(&pattern[1..], &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.pak", "re_chunk_000.pak"));
+        assert!(!glob_match("*.pak", "dlc/re_dlc_stm_3308900.pak"));
+        assert!(glob_match("**/*.pak", "dlc/re_dlc_stm_3308900.pak"));
+        assert!(glob_match("**/*.pak", "re_chunk_000.pak"));
+        assert!(glob_match("dlc/**", "dlc/a/b.pak"));
+        assert!(glob_match("re_chunk_00?.pak", "RE_CHUNK_001.PAK"));
+        assert!(!glob_match("re_chunk_00?.pak", "re_chunk_0001.pak"));
+    }
+
+    #[test]
+    fn test_discover() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let game_dir = temp_dir.path();
+        fs::create_dir_all(game_dir.join("dlc")).unwrap();
+        fs::create_dir_all(game_dir.join("mods/nested")).unwrap();
+        for path in [
+            "re_chunk_000.pak",
+            "re_chunk_000.pak.sub_000.pak",
+            "some_mod.pak",
+            "readme.txt",
+            "dlc/re_dlc_stm_3308900.pak",
+            "mods/re_chunk_000.pak.patch_010.pak",
+            "mods/nested/re_chunk_000.pak.patch_011.pak",
+        ] {
+            fs::write(game_dir.join(path), b"").unwrap();
+        }
+
+        let options = DiscoveryOptions {
+            roots: vec![PathBuf::from("."), PathBuf::from("dlc")],
+            max_depth: 1,
+            exclude: vec!["mods/**".to_string()],
+            ..Default::default()
+        };
+        let discovery = discover(game_dir, &options).unwrap();
+
+        let names = discovery
+            .chunks
+            .iter()
+            .map(|c| c.chunk_name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "re_chunk_000.pak",
+                "re_dlc_stm_3308900.pak",
+                "re_chunk_000.pak.sub_000.pak"
+            ]
+        );
+
+        let skipped = discovery
+            .skipped
+            .iter()
+            .map(|s| {
+                (
+                    s.path.file_name().unwrap().to_string_lossy().to_string(),
+                    s.reason.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert!(skipped.contains(&("mods".to_string(), SkipReason::Excluded)));
+        assert!(skipped.iter().any(|(name, reason)| name == "some_mod.pak"
+            && matches!(reason, SkipReason::InvalidChunkName(_))));
+        // dlc is reached from "." first, the "dlc" root is not scanned twice
+        assert_eq!(
+            discovery
+                .chunks
+                .iter()
+                .filter(|c| c.chunk_name.to_string() == "re_dlc_stm_3308900.pak")
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Install health report.
+
+use std::path::PathBuf;
+
+use crate::{
+    install::Severity,
+    patch_seq::{self, PatchFile, PatchKind},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PakClass {
+    Official,
+    Generated,
+    Placeholder,
+    Backup,
+    Temp,
+    /// Pak files that look like mods, see [`crate::mods::is_mod_pak`]
+    ThirdParty,
+}
+
+impl std::fmt::Display for PakClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PakClass::Official => write!(f, "official"),
+            PakClass::Generated => write!(f, "tool generated"),
+            PakClass::Placeholder => write!(f, "placeholder"),
+            PakClass::Backup => write!(f, "backup"),
+            PakClass::Temp => write!(f, "temporary"),
+            PakClass::ThirdParty => write!(f, "unknown third-party"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PakInfo {
+    pub path: PathBuf,
+    pub class: PakClass,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+    pub suggestion: &'static str,
+}
+
+#[derive(Debug, Default)]
+pub struct HealthReport {
+    pub paks: Vec<PakInfo>,
+    pub findings: Vec<Finding>,
+}
+
+impl HealthReport {
+    pub fn count(&self, class: PakClass) -> usize {
+        self.paks.iter().filter(|pak| pak.class == class).count()
+    }
+
+    pub fn add(&mut self, severity: Severity, message: String, suggestion: &'static str) {
+        self.findings.push(Finding {
+            severity,
+            message,
+            suggestion,
+        });
+    }
+}
+
+/// Check continuity and order of patch sequences
+pub fn check_patch_sequences(patches: &[PatchFile]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for series in patch_seq::group_series(patches) {
+        let series_name = series[0].chunk_name.series();
+
+        // the game stops at the first missing patch ID
+        let ids = series
+            .iter()
+            .filter_map(|p| p.chunk_name.last_patch_id())
+            .collect::<Vec<_>>();
+        if let Some(missing_id) = (1..).find(|id| !ids.contains(id))
+            && ids.iter().any(|id| *id > missing_id)
+        {
+            findings.push(Finding {
+                severity: Severity::Fatal,
+                message: format!(
+                    "{series_name}: patch {missing_id:03} is missing, later patches may not be loaded"
+                ),
+                suggestion: "Verify game files in Steam, then run Restore to compact generated patches",
+            });
+        }
+
+        let max_official_id = series
+            .iter()
+            .filter(|p| p.kind == PatchKind::Official)
+            .filter_map(|p| p.chunk_name.last_patch_id())
+            .max();
+        let generated_below = series
+            .iter()
+            .filter(|p| p.kind == PatchKind::Generated)
+            .filter(|p| p.chunk_name.last_patch_id() < max_official_id)
+            .count();
+        if generated_below > 0 {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                message: format!(
+                    "{series_name}: {generated_below} generated patches are loaded before official or third-party patches"
+                ),
+                suggestion: "Run Renumber patches",
+            });
+        }
+
+        let series_patches = series.iter().map(|p| (*p).clone()).collect::<Vec<_>>();
+        let (removals, _) = patch_seq::plan_compaction(&series_patches);
+        if !removals.is_empty() {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                message: format!(
+                    "{series_name}: {} placeholders are no longer needed",
+                    removals.len()
+                ),
+                suggestion: "Run Restore to clean them up",
+            });
+        }
+    }
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::ChunkName;
+
+    fn patch(name: &str, kind: PatchKind) -> PatchFile {
+        PatchFile {
+            chunk_name: ChunkName::try_from_str(name).unwrap(),
+            path: PathBuf::from(name),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_check_patch_sequences() {
+        let findings = check_patch_sequences(&[
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_001.pak",
+                PatchKind::Generated,
+            ),
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_003.pak",
+                PatchKind::Official,
+            ),
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_004.pak",
+                PatchKind::Placeholder,
+            ),
+        ]);
+        assert_eq!(findings.len(), 3);
+        assert_eq!(findings[0].severity, Severity::Fatal);
+        assert!(findings[0].message.contains("patch 002 is missing"));
+
+        let findings = check_patch_sequences(&[
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_001.pak",
+                PatchKind::Official,
+            ),
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_002.pak",
+                PatchKind::Generated,
+            ),
+        ]);
+        assert!(findings.is_empty());
+    }
+}
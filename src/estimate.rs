@@ -0,0 +1,188 @@
+//! Output size estimation.
+//!
+//! Decompressed tex size is calculated from the tex header, without
+//! decompressing any mipmaps.
+//!
+//! Tex header structure (version >= 190820018):
+//! - 0x00 magic `TEX\0`
+//! - 0x04 version
+//! - 0x08 width, height, depth (u16)
+//! - 0x0E image count (u8), mip header size (u8, mip count * 16)
+//! - 0x10 ~ 0x28 format, swizzle and other fields
+//! - 0x28 mip headers: `[offset: u64, pitch: u32, size: u32]` per mip per image
+
+use std::{
+    io::{self, Read},
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use fs_err as fs;
+use parking_lot::Mutex;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use ree_pak_core::read::archive::PakArchiveReader;
+
+use crate::tex_index::TexIndex;
+
+const TEX_MAGIC: &[u8; 4] = b"TEX\0";
+const TEX_HEADER_SIZE: usize = 0x28;
+const TEX_MIP_HEADER_SIZE: usize = 16;
+/// Pak header size
+const PAK_HEADER_SIZE: u64 = 16;
+/// Pak TOC entry size
+const PAK_ENTRY_SIZE: u64 = 48;
+
+/// Read tex header from reader and calculate the decompressed tex size.
+///
+/// Returns None if the data is not a supported tex file.
+pub fn read_decompressed_tex_size<R>(reader: &mut R) -> io::Result<Option<u64>>
+where
+    R: Read,
+{
+    let mut header = [0u8; TEX_HEADER_SIZE];
+    if reader.read_exact(&mut header).is_err() || &header[0..4] != TEX_MAGIC {
+        return Ok(None);
+    }
+
+    let image_count = header[0x0E] as usize;
+    let mip_count = header[0x0F] as usize / TEX_MIP_HEADER_SIZE;
+    let mip_header_count = image_count * mip_count;
+    if mip_header_count == 0 {
+        return Ok(None);
+    }
+
+    let mut mip_headers = vec![0u8; mip_header_count * TEX_MIP_HEADER_SIZE];
+    if reader.read_exact(&mut mip_headers).is_err() {
+        return Ok(None);
+    }
+    let data_size = mip_headers
+        .chunks_exact(TEX_MIP_HEADER_SIZE)
+        .map(|mip_header| u32::from_le_bytes(mip_header[12..16].try_into().unwrap()) as u64)
+        .sum::<u64>();
+
+    Ok(Some(
+        (TEX_HEADER_SIZE + mip_headers.len()) as u64 + data_size,
+    ))
+}
+
+/// Estimate the size of the output pak generated from the input pak.
+pub fn estimate_output_size(
+    input_path: &Path,
+    tex_index: &TexIndex,
+    use_full_package_mode: bool,
+) -> color_eyre::Result<u64> {
+    let file = fs::File::open(input_path)?;
+    let mut reader = io::BufReader::new(file);
+
+    let pak_archive = ree_pak_core::read::read_archive(&mut reader)?;
+    let archive_reader_mtx = Mutex::new(PakArchiveReader::new(reader, &pak_archive));
+
+    let entries = pak_archive
+        .entries()
+        .iter()
+        .filter(|entry| use_full_package_mode || tex_index.contains(entry.hash()))
+        .collect::<Vec<_>>();
+
+    let total_size = AtomicU64::new(0);
+    entries
+        .par_iter()
+        .try_for_each(|&entry| -> color_eyre::Result<()> {
+            let size = if tex_index.contains(entry.hash()) {
+                let mut entry_reader = {
+                    let mut archive_reader = archive_reader_mtx.lock();
+                    archive_reader.owned_entry_reader(entry.clone())?
+                };
+                read_decompressed_tex_size(&mut entry_reader)?.unwrap_or(entry.decompressed_size())
+            } else {
+                entry.decompressed_size()
+            };
+            total_size.fetch_add(size, Ordering::Relaxed);
+            Ok(())
+        })?;
+
+    // +1 for metadata
+    let toc_size = PAK_HEADER_SIZE + PAK_ENTRY_SIZE * (entries.len() as u64 + 1);
+    Ok(total_size.load(Ordering::Relaxed) + toc_size)
+}
+
+/// Split entries into consecutive parts whose estimated pak size stays within
+/// the limit, returns the entry count of each part.
+///
+/// Each part holds at least one entry, so an entry larger than the limit gets
+/// a part of its own.
+pub fn split_by_size(entry_sizes: &[u64], max_part_size: u64) -> Vec<usize> {
+    // header and metadata entry
+    let part_overhead = PAK_HEADER_SIZE + PAK_ENTRY_SIZE;
+
+    let mut parts = Vec::new();
+    let mut part_len = 0;
+    let mut part_size = part_overhead;
+    for size in entry_sizes {
+        let size = size + PAK_ENTRY_SIZE;
+        if part_len > 0 && part_size + size > max_part_size {
+            parts.push(part_len);
+            part_len = 0;
+            part_size = part_overhead;
+        }
+        part_len += 1;
+        part_size += size;
+    }
+    if part_len > 0 {
+        parts.push(part_len);
+    }
+    parts
+}
+
+/// Available space on the volume containing the path.
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    fs4::available_space(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_tex(image_count: u8, mip_sizes: &[u32]) -> Vec<u8> {
+        let mut data = vec![0u8; TEX_HEADER_SIZE];
+        data[0..4].copy_from_slice(TEX_MAGIC);
+        data[4..8].copy_from_slice(&241106027u32.to_le_bytes());
+        data[0x0E] = image_count;
+        data[0x0F] = (mip_sizes.len() * TEX_MIP_HEADER_SIZE) as u8;
+        for _ in 0..image_count {
+            for size in mip_sizes {
+                data.extend_from_slice(&0u64.to_le_bytes());
+                data.extend_from_slice(&0u32.to_le_bytes());
+                data.extend_from_slice(&size.to_le_bytes());
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_read_decompressed_tex_size() {
+        let tex = make_tex(1, &[4096, 1024, 256]);
+        let size = read_decompressed_tex_size(&mut tex.as_slice()).unwrap();
+        assert_eq!(size, Some(0x28 + 3 * 16 + 4096 + 1024 + 256));
+
+        let cubemap = make_tex(6, &[64]);
+        let size = read_decompressed_tex_size(&mut cubemap.as_slice()).unwrap();
+        assert_eq!(size, Some(0x28 + 6 * 16 + 6 * 64));
+
+        let not_tex = b"MESH\0\0\0\0";
+        let size = read_decompressed_tex_size(&mut not_tex.as_slice()).unwrap();
+        assert_eq!(size, None);
+    }
+
+    #[test]
+    fn test_split_by_size() {
+        let overhead = PAK_HEADER_SIZE + PAK_ENTRY_SIZE;
+        let max_part_size = overhead + 2 * (100 + PAK_ENTRY_SIZE);
+        assert_eq!(split_by_size(&[100, 100, 100], max_part_size), vec![2, 1]);
+        // oversized entry gets its own part
+        assert_eq!(
+            split_by_size(&[100, 1000, 100], max_part_size),
+            vec![1, 1, 1]
+        );
+        assert!(split_by_size(&[], max_part_size).is_empty());
+    }
+}
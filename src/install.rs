@@ -0,0 +1,196 @@
+//! Game install validation.
+
+use std::{io, path::Path};
+
+use crate::{chunk::ChunkName, profile::GameProfile, tex_index::TexIndex};
+use fs_err as fs;
+
+/// Max number of entries sampled for known hash check
+const HASH_SAMPLE_SIZE: usize = 1000;
+/// Min ratio of known textures in sub chunks, which mostly contain textures
+const MIN_KNOWN_TEXTURE_RATIO: f64 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Fatal,
+}
+
+#[derive(Debug)]
+pub enum InstallIssue {
+    /// The expected executable is missing, other executables found in the directory
+    MissingExecutable {
+        expected: String,
+        other_executables: Vec<String>,
+    },
+    /// No base chunk in the directory
+    MissingBaseChunk(String),
+    /// Base chunk exists but there are no sub chunks
+    MissingSubChunks,
+    /// Pak files with names we cannot understand
+    UnknownPakNames(Vec<String>),
+    /// Pak file cannot be read, usually encrypted or an unsupported pak version
+    UnreadablePak { file_name: String, error: String },
+    /// Too few entries in sub chunks are textures known by the file name list
+    UnknownHashes { known: usize, sampled: usize },
+}
+
+impl InstallIssue {
+    pub fn severity(&self) -> Severity {
+        match self {
+            // other executables usually mean another game's directory
+            InstallIssue::MissingExecutable {
+                other_executables, ..
+            } if !other_executables.is_empty() => Severity::Fatal,
+            InstallIssue::MissingExecutable { .. } => Severity::Warning,
+            InstallIssue::MissingBaseChunk(_) => Severity::Fatal,
+            InstallIssue::MissingSubChunks => Severity::Warning,
+            InstallIssue::UnknownPakNames(_) => Severity::Warning,
+            InstallIssue::UnreadablePak { .. } => Severity::Fatal,
+            InstallIssue::UnknownHashes { .. } => Severity::Fatal,
+        }
+    }
+}
+
+impl std::fmt::Display for InstallIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstallIssue::MissingExecutable {
+                expected,
+                other_executables,
+            } => {
+                write!(f, "{expected} not found")?;
+                if !other_executables.is_empty() {
+                    write!(
+                        f,
+                        ", found {} instead. This may be another game's directory.",
+                        other_executables.join(", ")
+                    )?;
+                }
+                Ok(())
+            }
+            InstallIssue::MissingBaseChunk(base_chunk) => write!(
+                f,
+                "{base_chunk} not found. This is not a game directory, or the install is incomplete."
+            ),
+            InstallIssue::MissingSubChunks => write!(
+                f,
+                "No sub chunk files found. The install may be incomplete, verify game files in Steam."
+            ),
+            InstallIssue::UnknownPakNames(names) => {
+                write!(f, "Pak files with unknown names: {}", names.join(", "))
+            }
+            InstallIssue::UnreadablePak { file_name, error } => write!(
+                f,
+                "Failed to read {file_name}, it may be encrypted or an unsupported pak version: {error}"
+            ),
+            InstallIssue::UnknownHashes { known, sampled } => write!(
+                f,
+                "Only {known} of {sampled} sampled files are known textures. This may be another RE Engine game."
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct InstallReport {
+    pub issues: Vec<InstallIssue>,
+}
+
+impl InstallReport {
+    pub fn severity(&self) -> Option<Severity> {
+        self.issues.iter().map(InstallIssue::severity).max()
+    }
+}
+
+/// Check if the directory is a valid game install.
+pub fn validate_install(
+    game_dir: &Path,
+    profile: &GameProfile,
+    tex_index: &TexIndex,
+) -> color_eyre::Result<InstallReport> {
+    let mut report = InstallReport::default();
+
+    let mut executables = Vec::new();
+    let mut unknown_pak_names = Vec::new();
+    let mut sub_chunk_paths = Vec::new();
+    for entry in fs::read_dir(game_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let file_name = entry.file_name().to_string_lossy().to_string();
+
+        if file_name.to_ascii_lowercase().ends_with(".exe") {
+            executables.push(file_name);
+        } else if file_name.ends_with(".pak") {
+            match ChunkName::try_from_str_strict(&file_name) {
+                Ok(chunk_name) if chunk_name.sub_id().is_some() && !chunk_name.is_patch() => {
+                    sub_chunk_paths.push(entry.path())
+                }
+                Ok(_) => {}
+                Err(_) => unknown_pak_names.push(file_name),
+            }
+        }
+    }
+
+    if !executables
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(&profile.executable))
+    {
+        report.issues.push(InstallIssue::MissingExecutable {
+            expected: profile.executable.clone(),
+            other_executables: executables,
+        });
+    }
+    if !unknown_pak_names.is_empty() {
+        report
+            .issues
+            .push(InstallIssue::UnknownPakNames(unknown_pak_names));
+    }
+
+    let base_chunk_path = game_dir.join(&profile.base_chunk);
+    if !base_chunk_path.is_file() {
+        report
+            .issues
+            .push(InstallIssue::MissingBaseChunk(profile.base_chunk.clone()));
+        return Ok(report);
+    }
+    if sub_chunk_paths.is_empty() {
+        report.issues.push(InstallIssue::MissingSubChunks);
+    }
+
+    // the base chunk must be readable
+    let file = fs::File::open(&base_chunk_path)?;
+    let mut reader = io::BufReader::new(file);
+    if let Err(e) = ree_pak_core::read::read_archive(&mut reader) {
+        report.issues.push(InstallIssue::UnreadablePak {
+            file_name: profile.base_chunk.clone(),
+            error: e.to_string(),
+        });
+        return Ok(report);
+    }
+
+    // check a sample of sub chunk hashes against the texture index
+    let (mut known, mut sampled) = (0, 0);
+    let sample_size = HASH_SAMPLE_SIZE / sub_chunk_paths.len().max(1);
+    for sub_chunk_path in &sub_chunk_paths {
+        let mut reader = io::BufReader::new(fs::File::open(sub_chunk_path)?);
+        let Ok(pak_archive) = ree_pak_core::read::read_archive(&mut reader) else {
+            continue;
+        };
+        let entries = pak_archive.entries();
+        let step = (entries.len() / sample_size.max(1)).max(1);
+        for entry in entries.iter().step_by(step).take(sample_size.max(1)) {
+            known += tex_index.contains(entry.hash()) as usize;
+            sampled += 1;
+        }
+    }
+    if sampled > 0 && (known as f64 / sampled as f64) < MIN_KNOWN_TEXTURE_RATIO {
+        report
+            .issues
+            .push(InstallIssue::UnknownHashes { known, sampled });
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,125 @@
+//! Files left behind by interrupted or outdated runs.
+
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Leftover {
+    /// Backup whose original pak is missing
+    OrphanedBackup { backup_path: PathBuf },
+    /// Backup whose pak is not generated by this tool, usually replaced by a game update
+    StaleBackup { backup_path: PathBuf },
+    /// Temporary output of an interrupted replace mode run
+    TempFile { temp_path: PathBuf },
+    /// Generated output that is not a valid pak, interrupted while writing
+    PartialOutput { path: PathBuf },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fix {
+    RestoreBackup,
+    Delete,
+    Keep,
+}
+
+impl std::fmt::Display for Fix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fix::RestoreBackup => write!(f, "Restore backup"),
+            Fix::Delete => write!(f, "Delete"),
+            Fix::Keep => write!(f, "Keep as is"),
+        }
+    }
+}
+
+impl Leftover {
+    pub fn path(&self) -> &Path {
+        match self {
+            Leftover::OrphanedBackup { backup_path } => backup_path,
+            Leftover::StaleBackup { backup_path } => backup_path,
+            Leftover::TempFile { temp_path } => temp_path,
+            Leftover::PartialOutput { path } => path,
+        }
+    }
+
+    /// The pak file this leftover belongs to
+    pub fn live_path(&self) -> PathBuf {
+        match self {
+            Leftover::PartialOutput { path } => path.clone(),
+            // strip `.backup` or `.temp`
+            _ => self.path().with_extension(""),
+        }
+    }
+
+    /// Available fixes, the recommended one first
+    pub fn fixes(&self) -> &'static [Fix] {
+        match self {
+            Leftover::OrphanedBackup { .. } => &[Fix::RestoreBackup, Fix::Keep, Fix::Delete],
+            Leftover::StaleBackup { .. } => &[Fix::Delete, Fix::Keep, Fix::RestoreBackup],
+            Leftover::TempFile { .. } => &[Fix::Delete, Fix::Keep],
+            Leftover::PartialOutput { .. } => &[Fix::Delete, Fix::Keep],
+        }
+    }
+
+    pub fn apply(&self, fix: Fix) -> color_eyre::Result<()> {
+        match fix {
+            Fix::Keep => {}
+            Fix::Delete => fs::remove_file(self.path())?,
+            Fix::RestoreBackup => {
+                let live_path = self.live_path();
+                if live_path.exists() {
+                    fs::remove_file(&live_path)?;
+                }
+                fs::rename(self.path(), live_path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Leftover {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let file_name = self.path().file_name().unwrap().to_string_lossy();
+        match self {
+            Leftover::OrphanedBackup { .. } => write!(
+                f,
+                "{file_name}: backup without the original file, restoring it brings the original file back"
+            ),
+            Leftover::StaleBackup { .. } => write!(
+                f,
+                "{file_name}: the original file is not generated by this tool anymore, probably replaced by a game update, so the backup is outdated"
+            ),
+            Leftover::TempFile { .. } => write!(
+                f,
+                "{file_name}: temporary file of an interrupted run, it is not used by the game"
+            ),
+            Leftover::PartialOutput { .. } => write!(
+                f,
+                "{file_name}: generated file is incomplete, probably interrupted while writing, the game may fail to load it"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore_backup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let backup_path = temp_dir.path().join("re_chunk_000.pak.sub_000.pak.backup");
+        fs::write(&backup_path, "original").unwrap();
+
+        let leftover = Leftover::OrphanedBackup {
+            backup_path: backup_path.clone(),
+        };
+        let live_path = temp_dir.path().join("re_chunk_000.pak.sub_000.pak");
+        assert_eq!(leftover.live_path(), live_path);
+
+        leftover.apply(Fix::RestoreBackup).unwrap();
+        assert!(!backup_path.exists());
+        assert_eq!(fs::read_to_string(&live_path).unwrap(), "original");
+    }
+}
@@ -1,7 +1,25 @@
 mod app;
+mod backup;
 mod chunk;
 mod component;
+mod compression;
+mod diff;
+mod discovery;
+mod doctor;
+mod estimate;
+mod install;
+mod leftover;
 mod metadata;
+mod mods;
+mod name_list;
+mod pack;
+mod patch_seq;
+mod profile;
+mod record;
+mod sniff;
+mod steam;
+mod tex_index;
+mod unknown_hash;
 mod updater;
 mod util;
 
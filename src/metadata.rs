@@ -1,6 +1,9 @@
 //! Extended metadata for generated pak files.
 
-use std::io::{self, Read, Write};
+use std::{
+    io::{self, Read, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use ree_pak_core::{
     pak::PakArchive,
@@ -10,26 +13,126 @@ use ree_pak_core::{
 };
 use serde::{Deserialize, Serialize};
 
+use crate::compression::EntryCompression;
+
 const METADATA_KEY: &str = "__TEX_DECOMPRESSOR_METADATA__";
+const METADATA_VERSION: u32 = 6;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PakMetadata {
     version: u32,
     is_full_package: bool,
+    /// Source chunk file name, since version 2
+    #[serde(default)]
+    source: Option<String>,
+    /// Creation time in seconds since the Unix epoch, since version 2
+    #[serde(default)]
+    created_at: Option<u64>,
+    /// Source chunk file names of merged outputs, since version 3
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    merged_sources: Vec<String>,
+    /// Part of an output split into several files, since version 4
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    part: Option<OutputPart>,
+    /// Compression of non-texture entries, since version 5
+    #[serde(default)]
+    compression: EntryCompression,
+    /// What produced the file, since version 6
+    #[serde(default)]
+    kind: OutputKind,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputKind {
+    /// Decompressed chunk or patch, managed by restore and renumbering
+    #[default]
+    Decompressed,
+    /// Loose files packed by the user, treated like a mod
+    Packed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputPart {
+    /// Logical output name, the file name of the first part when created
+    pub output: String,
+    /// Zero-based part index, parts are loaded in index order
+    pub index: u32,
+    pub count: u32,
 }
 
 impl PakMetadata {
     pub fn new(is_full_package: bool) -> Self {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .ok();
         Self {
-            version: 1,
+            version: METADATA_VERSION,
             is_full_package,
+            source: None,
+            created_at,
+            merged_sources: Vec::new(),
+            part: None,
+            compression: EntryCompression::Store,
+            kind: OutputKind::Decompressed,
         }
     }
 
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    pub fn with_merged_sources(mut self, sources: Vec<String>) -> Self {
+        self.merged_sources = sources;
+        self
+    }
+
+    pub fn with_part(mut self, part: OutputPart) -> Self {
+        self.part = Some(part);
+        self
+    }
+
+    pub fn with_compression(mut self, compression: EntryCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn with_kind(mut self, kind: OutputKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
     pub fn is_full_package(&self) -> bool {
         self.is_full_package
     }
 
+    /// Source chunk file names, empty if unknown
+    pub fn sources(&self) -> Vec<&str> {
+        if self.merged_sources.is_empty() {
+            self.source.as_deref().into_iter().collect()
+        } else {
+            self.merged_sources.iter().map(String::as_str).collect()
+        }
+    }
+
+    pub fn part(&self) -> Option<&OutputPart> {
+        self.part.as_ref()
+    }
+
+    pub fn compression(&self) -> EntryCompression {
+        self.compression
+    }
+
+    pub fn kind(&self) -> OutputKind {
+        self.kind
+    }
+
+    pub fn created_at(&self) -> Option<u64> {
+        self.created_at
+    }
+
     pub fn from_pak_archive<R>(
         reader: R,
         pak_archive: &PakArchive,
@@ -69,3 +172,20 @@ impl PakMetadata {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind_default() {
+        // written before version 6
+        let metadata: PakMetadata =
+            serde_json::from_str(r#"{"version":5,"is_full_package":false}"#).unwrap();
+        assert_eq!(metadata.kind(), OutputKind::Decompressed);
+
+        let json =
+            serde_json::to_string(&PakMetadata::new(false).with_kind(OutputKind::Packed)).unwrap();
+        assert!(json.contains(r#""kind":"packed""#));
+    }
+}
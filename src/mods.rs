@@ -0,0 +1,413 @@
+//! Mod detection.
+//!
+//! Mods come as extra pak files, loose files under `natives/` loaded by a mod
+//! loader, or both. Generated patches contain whole textures, so any texture a
+//! mod also touches ends up overridden on one side.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+use ree_pak_core::utf16_hash::Utf16HashExt;
+
+use crate::{chunk::ChunkName, record::Record};
+
+/// DLLs commonly used to inject mod loaders like REFramework
+pub const LOADER_DLLS: &[&str] = &["dinput8.dll", "winmm.dll", "version.dll", "dxgi.dll"];
+/// Loose file directory read by mod loaders
+pub const LOOSE_FILE_DIR: &str = "natives";
+
+#[derive(Debug, Clone)]
+pub enum ModSource {
+    /// Pak file not generated by this tool
+    Pak(PathBuf),
+    /// Loose files directory
+    LooseFiles(PathBuf),
+}
+
+impl std::fmt::Display for ModSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModSource::Pak(path) => write!(f, "mod pak {}", path.display()),
+            ModSource::LooseFiles(path) => write!(f, "loose files in {}", path.display()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DetectedMod {
+    pub source: ModSource,
+    /// Entry hashes of the mod files
+    pub hashes: HashSet<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverrideSource {
+    /// Index into [`Overrides::paks`]
+    Pak(usize),
+    LooseFile,
+    /// Chunk loaded after the source chunk of a merged output
+    Superseded,
+}
+
+/// Files supplied by paks and loose files loaded after an output, which take
+/// effect over the output's own files.
+#[derive(Debug, Default)]
+pub struct Overrides {
+    paks: Vec<PathBuf>,
+    sources: HashMap<u64, OverrideSource>,
+    /// Decompress textures from the overriding paks instead of skipping them
+    pub decompress_mod_textures: bool,
+}
+
+impl Overrides {
+    pub fn new(decompress_mod_textures: bool) -> Self {
+        Self {
+            decompress_mod_textures,
+            ..Default::default()
+        }
+    }
+
+    /// Add a pak file, must be called in load order
+    pub fn add_pak(&mut self, path: &Path, hashes: &HashSet<u64>) {
+        let index = self.paks.len();
+        self.paks.push(path.to_path_buf());
+        for hash in hashes {
+            self.sources.insert(*hash, OverrideSource::Pak(index));
+        }
+    }
+
+    /// Add loose files, they are loaded after all paks
+    pub fn add_loose_files(&mut self, hashes: &HashSet<u64>) {
+        for hash in hashes {
+            self.sources.insert(*hash, OverrideSource::LooseFile);
+        }
+    }
+
+    /// Add entries of merged chunks that are overridden by other chunks,
+    /// must be called before adding paks
+    pub fn add_superseded(&mut self, hashes: &HashSet<u64>) {
+        for hash in hashes {
+            self.sources.insert(*hash, OverrideSource::Superseded);
+        }
+    }
+
+    pub fn paks(&self) -> &[PathBuf] {
+        &self.paks
+    }
+
+    pub fn source(&self, hash: u64) -> Option<OverrideSource> {
+        self.sources.get(&hash).copied()
+    }
+
+    /// Whether the output should keep an entry, and read it from the overriding pak
+    pub fn keeps(&self, hash: u64) -> bool {
+        match self.source(hash) {
+            None => true,
+            Some(OverrideSource::Pak(_)) => self.decompress_mod_textures,
+            Some(OverrideSource::LooseFile | OverrideSource::Superseded) => false,
+        }
+    }
+}
+
+/// Check if a pak file is a mod.
+///
+/// Mods have no tool metadata, and are not official in the install baseline.
+/// Official patches are never moved, so mods moved up by this tool stay mods.
+/// Without a baseline nothing is a mod.
+pub fn is_mod_pak(record: &Record, game_dir: &Path, file_path: &Path, has_metadata: bool) -> bool {
+    let Some(baseline) = &record.baseline else {
+        return false;
+    };
+    !has_metadata && !baseline.is_official(game_dir, file_path)
+}
+
+/// Check if a pak file is loaded after a chunk, pak files with custom names
+/// are assumed to be loaded last
+pub fn is_loaded_after(path: &Path, chunk_name: &ChunkName) -> bool {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    match ChunkName::try_from_str(&file_name) {
+        Ok(pak_chunk_name) => pak_chunk_name > *chunk_name,
+        Err(_) => true,
+    }
+}
+
+/// Entries of input chunks overridden by other chunks loaded after them.
+///
+/// Chunk names sort in load order, so the last chunk containing an entry wins.
+pub fn superseded_hashes(
+    chunks: &[(ChunkName, HashSet<u64>)],
+    inputs: &[ChunkName],
+) -> HashSet<u64> {
+    let mut sorted_chunks = chunks.iter().collect::<Vec<_>>();
+    sorted_chunks.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut last_is_input: HashMap<u64, bool> = HashMap::new();
+    for (chunk_name, hashes) in &sorted_chunks {
+        let is_input = inputs.contains(chunk_name);
+        for hash in hashes {
+            last_is_input.insert(*hash, is_input);
+        }
+    }
+
+    sorted_chunks
+        .iter()
+        .filter(|(chunk_name, _)| inputs.contains(chunk_name))
+        .flat_map(|(_, hashes)| hashes)
+        .filter(|hash| last_is_input.get(*hash) == Some(&false))
+        .copied()
+        .collect()
+}
+
+/// Loader DLLs found in the game directory
+pub fn find_loader_dlls(game_dir: &Path) -> Vec<&'static str> {
+    LOADER_DLLS
+        .iter()
+        .copied()
+        .filter(|name| game_dir.join(name).is_file())
+        .collect()
+}
+
+/// Read entry hashes of a pak file
+pub fn read_pak_hashes(path: &Path) -> color_eyre::Result<HashSet<u64>> {
+    let file = fs::File::open(path)?;
+    let mut reader = io::BufReader::new(file);
+    let pak_archive = ree_pak_core::read::read_archive(&mut reader)?;
+    Ok(pak_archive
+        .entries()
+        .iter()
+        .map(|entry| entry.hash())
+        .collect())
+}
+
+/// Hash paths of loose files under `natives/`, `None` if the directory does not exist
+pub fn scan_loose_files(game_dir: &Path) -> color_eyre::Result<Option<DetectedMod>> {
+    let natives_dir = game_dir.join(LOOSE_FILE_DIR);
+    if !natives_dir.is_dir() {
+        return Ok(None);
+    }
+
+    let mut hashes = HashSet::new();
+    let mut dirs = vec![natives_dir.clone()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            // pak paths are relative to the game directory, e.g. `natives/STM/...`
+            let relative_path = path
+                .strip_prefix(game_dir)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            hashes.insert(relative_path.as_str().hash_mixed());
+        }
+    }
+
+    Ok(Some(DetectedMod {
+        source: ModSource::LooseFiles(natives_dir),
+        hashes,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::{Baseline, BaselineFile};
+
+    #[test]
+    fn test_is_mod_pak() {
+        let game_dir = Path::new("game");
+        let path = |name: &str| game_dir.join(name);
+        let mut record = Record::default();
+        assert!(!is_mod_pak(&record, game_dir, &path("mod.pak"), false));
+
+        // a mod patch is installed before the first run
+        record.baseline = Some(Baseline::new(
+            &path("missing.exe"),
+            vec![
+                BaselineFile::new(game_dir, &path("re_chunk_000.pak"), 100, false),
+                BaselineFile::new(game_dir, &path("re_chunk_000.pak.patch_001.pak"), 10, false),
+                BaselineFile::new(game_dir, &path("re_chunk_000.pak.patch_002.pak"), 5, false),
+            ],
+        ));
+        // patches of an unconfirmed baseline are not trusted
+        assert!(!is_mod_pak(
+            &record,
+            game_dir,
+            &path("re_chunk_000.pak"),
+            false
+        ));
+        assert!(is_mod_pak(
+            &record,
+            game_dir,
+            &path("re_chunk_000.pak.patch_002.pak"),
+            false
+        ));
+        assert!(is_mod_pak(
+            &record,
+            game_dir,
+            &path("re_chunk_000.pak.patch_001.pak"),
+            false
+        ));
+
+        let baseline = record.baseline.as_mut().unwrap();
+        assert_eq!(
+            baseline.unconfirmed_patches(),
+            vec![
+                "re_chunk_000.pak.patch_001.pak",
+                "re_chunk_000.pak.patch_002.pak"
+            ]
+        );
+        baseline.verify(vec!["re_chunk_000.pak.patch_002.pak".to_string()]);
+        assert!(is_mod_pak(
+            &record,
+            game_dir,
+            &path("re_chunk_000.pak.patch_002.pak"),
+            false
+        ));
+        // official patches of the base chunk are not mods
+        assert!(!is_mod_pak(
+            &record,
+            game_dir,
+            &path("re_chunk_000.pak.patch_001.pak"),
+            false
+        ));
+        // patches of sub chunks can be mods too
+        assert!(is_mod_pak(
+            &record,
+            game_dir,
+            &path("re_chunk_000.pak.sub_000.pak.patch_001.pak"),
+            false
+        ));
+        assert!(is_mod_pak(&record, game_dir, &path("mod.pak"), false));
+        assert!(!is_mod_pak(
+            &record,
+            game_dir,
+            &path("re_chunk_000.pak.patch_003.pak"),
+            true
+        ));
+    }
+
+    #[test]
+    fn test_is_mod_pak_after_shifts() {
+        let game_dir = Path::new("game");
+        let path = |id: u32| game_dir.join(format!("re_chunk_000.pak.patch_{id:03}.pak"));
+        let mut record = Record::default();
+        let mut baseline = Baseline::new(
+            &game_dir.join("missing.exe"),
+            vec![
+                BaselineFile::new(game_dir, &path(1), 10, false),
+                BaselineFile::new(game_dir, &path(2), 5, false),
+            ],
+        );
+        baseline.verify(vec!["re_chunk_000.pak.patch_002.pak".to_string()]);
+        record.baseline = Some(baseline);
+
+        // each run inserts an output below the mod, moving it up
+        for (output_id, mod_id) in [(2, 3), (3, 4)] {
+            record.add_output(game_dir, &path(output_id), false);
+            record.set_shifted(
+                game_dir,
+                &path(output_id),
+                [(path(output_id).as_path(), path(mod_id).as_path())],
+            );
+            assert!(is_mod_pak(&record, game_dir, &path(mod_id), false));
+            assert!(!is_mod_pak(&record, game_dir, &path(output_id), true));
+        }
+        assert!(!is_mod_pak(&record, game_dir, &path(1), false));
+    }
+
+    #[test]
+    fn test_is_loaded_after() {
+        let output = ChunkName::try_from_str("re_chunk_000.pak.sub_000.pak.patch_003.pak").unwrap();
+        assert!(is_loaded_after(
+            Path::new("game/re_chunk_000.pak.sub_000.pak.patch_004.pak"),
+            &output
+        ));
+        assert!(!is_loaded_after(
+            Path::new("game/re_chunk_000.pak.sub_000.pak.patch_002.pak"),
+            &output
+        ));
+        assert!(is_loaded_after(Path::new("game/my_mod.pak"), &output));
+    }
+
+    #[test]
+    fn test_superseded_hashes() {
+        let name = |s: &str| ChunkName::try_from_str(s).unwrap();
+        let chunks = vec![
+            (
+                name("re_chunk_000.pak.sub_000.pak.patch_001.pak"),
+                HashSet::from([2, 3]),
+            ),
+            (
+                name("re_chunk_000.pak.sub_000.pak"),
+                HashSet::from([1, 2, 3]),
+            ),
+            (
+                name("re_chunk_000.pak.sub_000.pak.patch_002.pak"),
+                HashSet::from([3, 4]),
+            ),
+        ];
+        // the base and the first patch are merged, the second patch stays
+        let inputs = vec![
+            name("re_chunk_000.pak.sub_000.pak"),
+            name("re_chunk_000.pak.sub_000.pak.patch_001.pak"),
+        ];
+        assert_eq!(superseded_hashes(&chunks, &inputs), HashSet::from([3]));
+        // all merged, nothing loads after them
+        let inputs = chunks
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        assert!(superseded_hashes(&chunks, &inputs).is_empty());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut overrides = Overrides::new(true);
+        overrides.add_pak(Path::new("a.pak"), &HashSet::from([1, 2]));
+        overrides.add_pak(Path::new("b.pak"), &HashSet::from([2]));
+        overrides.add_loose_files(&HashSet::from([3]));
+
+        assert_eq!(overrides.source(1), Some(OverrideSource::Pak(0)));
+        // later pak wins
+        assert_eq!(overrides.source(2), Some(OverrideSource::Pak(1)));
+        assert!(overrides.keeps(2));
+        assert!(!overrides.keeps(3));
+        assert!(overrides.keeps(4));
+
+        overrides.decompress_mod_textures = false;
+        assert!(!overrides.keeps(2));
+
+        let mut overrides = Overrides::new(true);
+        overrides.add_superseded(&HashSet::from([1, 2]));
+        overrides.add_pak(Path::new("a.pak"), &HashSet::from([2]));
+        assert!(!overrides.keeps(1));
+        assert!(overrides.keeps(2));
+    }
+
+    #[test]
+    fn test_scan_loose_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let game_dir = temp_dir.path();
+        assert!(scan_loose_files(game_dir).unwrap().is_none());
+
+        let tex_dir = game_dir.join("natives/STM/Art/Model");
+        fs::create_dir_all(&tex_dir).unwrap();
+        fs::write(tex_dir.join("a.tex.241106027"), b"").unwrap();
+
+        let detected = scan_loose_files(game_dir).unwrap().unwrap();
+        assert!(
+            detected
+                .hashes
+                .contains(&"natives/STM/Art/Model/a.tex.241106027".hash_mixed())
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! File name list maintenance.
+//!
+//! List file format (zstd compressed):
+//! - Header lines: `#! @key: value`
+//! - Comment lines starting with `#` and empty lines are ignored
+//! - One file path per line, e.g. `natives/STM/Art/Model/xxx.tex.241106027`
+
+use std::collections::{HashMap, HashSet};
+
+use ree_pak_core::utf16_hash::Utf16HashExt;
+
+const ZSTD_LEVEL: i32 = 19;
+
+/// Parse a raw path list, returning normalized paths in their original order.
+pub fn parse_raw_list(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim_start_matches('\u{feff}').trim();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            let path = normalize_path(line);
+            if path.is_empty() { None } else { Some(path) }
+        })
+        .collect()
+}
+
+/// Normalize a path to the form used by pak files.
+///
+/// Backslashes are converted to slashes, leading `./` or `/` and duplicated
+/// separators are removed.
+pub fn normalize_path(path: &str) -> String {
+    let path = path.trim().trim_matches(|c| c == '\"' || c == '\'');
+    path.replace('\\', "/")
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Remove duplicated paths and sort the result.
+///
+/// Paths are hashed case-insensitively, so paths differing only in case are
+/// considered duplicates, and the first occurrence is kept.
+pub fn dedupe(paths: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut result = paths
+        .into_iter()
+        .filter(|path| seen.insert(path.to_lowercase()))
+        .collect::<Vec<_>>();
+    result.sort_by_key(|path| path.to_lowercase());
+    result
+}
+
+/// Keep only paths whose hash exists in the given hash set.
+pub fn retain_existing(paths: Vec<String>, hashes: &HashSet<u64>) -> Vec<String> {
+    paths
+        .into_iter()
+        .filter(|path| hashes.contains(&path.as_str().hash_mixed()))
+        .collect()
+}
+
+/// Decompress a list file and return its paths.
+pub fn read_list(data: &[u8]) -> color_eyre::Result<Vec<String>> {
+    let text = zstd::decode_all(data)?;
+    Ok(parse_raw_list(&String::from_utf8_lossy(&text)))
+}
+
+/// Build a zstd compressed list file with the given header fields.
+pub fn write_list(headers: &[(&str, &str)], paths: &[String]) -> color_eyre::Result<Vec<u8>> {
+    let mut text = String::new();
+    for (key, value) in headers {
+        text.push_str(&format!("#! @{key}: {value}\n"));
+    }
+    text.push('\n');
+    for path in paths {
+        text.push_str(path);
+        text.push('\n');
+    }
+
+    Ok(zstd::encode_all(text.as_bytes(), ZSTD_LEVEL)?)
+}
+
+/// Find paths whose hashes collide with each other.
+pub fn find_collisions(paths: &[String]) -> Vec<(String, String)> {
+    let mut by_hash: HashMap<u64, &String> = HashMap::new();
+    let mut collisions = Vec::new();
+    for path in paths {
+        if let Some(other) = by_hash.insert(path.as_str().hash_mixed(), path) {
+            collisions.push((other.clone(), path.clone()));
+        }
+    }
+    collisions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(
+            normalize_path("natives\\STM\\Art\\a.tex.241106027"),
+            "natives/STM/Art/a.tex.241106027"
+        );
+        assert_eq!(normalize_path("./natives//STM/a.tex"), "natives/STM/a.tex");
+        assert_eq!(
+            normalize_path("  \"/natives/STM/a.tex\" "),
+            "natives/STM/a.tex"
+        );
+    }
+
+    #[test]
+    fn test_parse_and_dedupe() {
+        let text = "\u{feff}#! @title: test\n\n# comment\nnatives/STM/b.tex\nnatives\\STM\\a.tex\nNATIVES/stm/B.TEX\n";
+        let paths = dedupe(parse_raw_list(text));
+        assert_eq!(paths, vec!["natives/STM/a.tex", "natives/STM/b.tex"]);
+    }
+
+    #[test]
+    fn test_write_and_read_list() {
+        let paths = vec![
+            "natives/STM/a.tex".to_string(),
+            "natives/STM/b.tex".to_string(),
+        ];
+        let data = write_list(&[("title", "test")], &paths).unwrap();
+        assert_eq!(read_list(&data).unwrap(), paths);
+    }
+}
@@ -0,0 +1,93 @@
+//! Packing loose files into a pak.
+
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+
+/// Top level directory of game resources
+const NATIVES_DIR: &str = "natives";
+
+/// A loose file to pack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackFile {
+    /// Path inside the pak, e.g. `natives/STM/...`
+    pub pak_path: String,
+    pub full_path: PathBuf,
+}
+
+impl PackFile {
+    pub fn is_tex(&self) -> bool {
+        self.pak_path.to_lowercase().contains(".tex.")
+    }
+}
+
+/// Collect files under a directory, sorted by pak path.
+///
+/// The directory is either the one containing `natives`, or `natives` itself.
+pub fn collect_files(root: &Path) -> color_eyre::Result<Vec<PackFile>> {
+    let prefix = match root.file_name() {
+        Some(name) if name.to_string_lossy().eq_ignore_ascii_case(NATIVES_DIR) => {
+            format!("{NATIVES_DIR}/")
+        }
+        _ => String::new(),
+    };
+
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let relative_path = path
+                .strip_prefix(root)
+                .unwrap()
+                .to_string_lossy()
+                .replace('\\', "/");
+            files.push(PackFile {
+                pak_path: format!("{prefix}{relative_path}"),
+                full_path: path,
+            });
+        }
+    }
+
+    files.sort_by(|a, b| a.pak_path.cmp(&b.pak_path));
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mod_dir = temp_dir.path().join("my_mod");
+        let tex_dir = mod_dir.join("natives/STM/Art");
+        fs::create_dir_all(&tex_dir).unwrap();
+        fs::write(tex_dir.join("a.tex.241106027"), b"").unwrap();
+        fs::write(tex_dir.join("b.mdf2.45"), b"").unwrap();
+
+        let files = collect_files(&mod_dir).unwrap();
+        let paths = files
+            .iter()
+            .map(|f| f.pak_path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                "natives/STM/Art/a.tex.241106027",
+                "natives/STM/Art/b.mdf2.45"
+            ]
+        );
+        assert!(files[0].is_tex());
+        assert!(!files[1].is_tex());
+
+        // natives directory itself
+        let files = collect_files(&mod_dir.join("natives")).unwrap();
+        assert_eq!(files[0].pak_path, "natives/STM/Art/a.tex.241106027");
+    }
+}
@@ -0,0 +1,402 @@
+//! Patch sequence management.
+//!
+//! Every chunk series (a base, DLC or sub chunk) has its own sequence of
+//! patches, loaded in patch ID order. Official patches must keep their IDs.
+//! Third-party patches are only moved up to insert an output below them, which
+//! is recorded and undone on restore. Generated patches can be moved around.
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::bail;
+use fs_err as fs;
+
+use crate::chunk::ChunkName;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchKind {
+    /// Shipped by the game
+    Official,
+    /// Pak without metadata that was added after the install baseline, usually a mod
+    ThirdParty,
+    /// Generated by this tool
+    Generated,
+    /// Empty patch generated by restore, only keeps the sequence continuous
+    Placeholder,
+}
+
+impl std::fmt::Display for PatchKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchKind::Official => write!(f, "official"),
+            PatchKind::ThirdParty => write!(f, "third-party"),
+            PatchKind::Generated => write!(f, "generated"),
+            PatchKind::Placeholder => write!(f, "placeholder"),
+        }
+    }
+}
+
+impl PatchKind {
+    /// Patches not managed by this tool, renumbering and compaction only move
+    /// generated patches above them
+    pub fn is_foreign(self) -> bool {
+        matches!(self, PatchKind::Official | PatchKind::ThirdParty)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PatchFile {
+    pub chunk_name: ChunkName,
+    pub path: PathBuf,
+    pub kind: PatchKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// Group patch files by chunk series and directory, patches sorted by ID
+pub fn group_series(patches: &[PatchFile]) -> Vec<Vec<&PatchFile>> {
+    let mut groups: Vec<Vec<&PatchFile>> = Vec::new();
+    for patch in patches.iter().filter(|p| p.chunk_name.is_patch()) {
+        let group = groups.iter_mut().find(|group| {
+            let first = group[0];
+            first.chunk_name.series() == patch.chunk_name.series()
+                && first.path.parent() == patch.path.parent()
+        });
+        match group {
+            Some(group) => group.push(patch),
+            None => groups.push(vec![patch]),
+        }
+    }
+    for group in groups.iter_mut() {
+        group.sort_by_key(|p| p.chunk_name.last_patch_id());
+    }
+    groups
+}
+
+/// Plan renames that move generated patches above the highest official or
+/// third-party patch of each series, keeping their relative order, and the
+/// placeholders to create in the IDs they leave below those patches.
+///
+/// Placeholders above official patches should be compacted first, or they may
+/// collide with the new names.
+pub fn plan_renumber(patches: &[PatchFile]) -> (Vec<Rename>, Vec<PathBuf>) {
+    let mut renames = Vec::new();
+    let mut placeholders = Vec::new();
+
+    for series in group_series(patches) {
+        let max_official_id = series
+            .iter()
+            .filter(|p| p.kind.is_foreign())
+            .filter_map(|p| p.chunk_name.last_patch_id())
+            .max()
+            .unwrap_or(0);
+        let generated = series
+            .iter()
+            .filter(|p| p.kind == PatchKind::Generated)
+            .collect::<Vec<_>>();
+
+        // nothing to do if all generated patches are already above official ones
+        if generated
+            .iter()
+            .all(|p| p.chunk_name.last_patch_id() > Some(max_official_id))
+        {
+            continue;
+        }
+
+        for (i, patch) in generated.iter().enumerate() {
+            let new_id = max_official_id + 1 + i as u32;
+            if patch.chunk_name.last_patch_id() == Some(new_id) {
+                continue;
+            }
+            let new_name = patch.chunk_name.with_patch(new_id);
+            renames.push(Rename {
+                from: patch.path.clone(),
+                to: patch.path.with_file_name(new_name.to_string()),
+            });
+            // the game stops loading at the first missing ID
+            if patch.chunk_name.last_patch_id() < Some(max_official_id) {
+                placeholders.push(patch.path.clone());
+            }
+        }
+    }
+
+    (renames, placeholders)
+}
+
+/// Plan removal of placeholders that are no longer needed, and renames that
+/// shift generated patches down to close the gaps.
+///
+/// Placeholders below an official patch are still needed, everything above the
+/// highest official patch is compacted.
+pub fn plan_compaction(patches: &[PatchFile]) -> (Vec<PathBuf>, Vec<Rename>) {
+    let mut removals = Vec::new();
+    let mut renames = Vec::new();
+
+    for series in group_series(patches) {
+        let max_official_id = series
+            .iter()
+            .filter(|p| p.kind.is_foreign())
+            .filter_map(|p| p.chunk_name.last_patch_id())
+            .max()
+            .unwrap_or(0);
+
+        let mut next_id = max_official_id + 1;
+        for patch in series
+            .iter()
+            .filter(|p| p.chunk_name.last_patch_id() > Some(max_official_id))
+        {
+            match patch.kind {
+                PatchKind::Placeholder => removals.push(patch.path.clone()),
+                PatchKind::Generated => {
+                    if patch.chunk_name.last_patch_id() != Some(next_id) {
+                        let new_name = patch.chunk_name.with_patch(next_id);
+                        renames.push(Rename {
+                            from: patch.path.clone(),
+                            to: patch.path.with_file_name(new_name.to_string()),
+                        });
+                    }
+                    next_id += 1;
+                }
+                PatchKind::Official | PatchKind::ThirdParty => unreachable!(),
+            }
+        }
+    }
+
+    (removals, renames)
+}
+
+/// Plan renames that free `insert_id` in a series, by moving the patch with
+/// that ID and all patches above it up by one.
+///
+/// Generated and third-party patches may be moved, fails if an official
+/// patch would be moved.
+pub fn plan_insert(series: &[PatchFile], insert_id: u32) -> color_eyre::Result<Vec<Rename>> {
+    if let Some(official) = series.iter().find(|patch| {
+        patch.kind == PatchKind::Official && patch.chunk_name.last_patch_id() >= Some(insert_id)
+    }) {
+        bail!(
+            "Can't insert patch {insert_id:03} below official patch {}",
+            official.path.display()
+        );
+    }
+
+    let renames = series
+        .iter()
+        .filter_map(|patch| {
+            let patch_id = patch.chunk_name.last_patch_id()?;
+            (patch_id >= insert_id).then(|| Rename {
+                from: patch.path.clone(),
+                to: patch
+                    .path
+                    .with_file_name(patch.chunk_name.with_patch(patch_id + 1).to_string()),
+            })
+        })
+        .collect();
+    Ok(renames)
+}
+
+/// Apply renames in two phases, so targets may overlap with sources.
+pub fn apply_renames(renames: &[Rename]) -> color_eyre::Result<()> {
+    let temp_paths = renames
+        .iter()
+        .map(|rename| temp_path(&rename.from))
+        .collect::<Vec<_>>();
+
+    for (rename, temp_path) in renames.iter().zip(&temp_paths) {
+        fs::rename(&rename.from, temp_path)?;
+    }
+    for (rename, temp_path) in renames.iter().zip(&temp_paths) {
+        fs::rename(temp_path, &rename.to)?;
+    }
+
+    Ok(())
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap().to_os_string();
+    file_name.push(".renumber");
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(name: &str, kind: PatchKind) -> PatchFile {
+        PatchFile {
+            chunk_name: ChunkName::try_from_str(name).unwrap(),
+            path: PathBuf::from("game").join(name),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_plan_renumber() {
+        let patches = vec![
+            patch("re_chunk_000.pak.sub_000.pak", PatchKind::Official),
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_001.pak",
+                PatchKind::Official,
+            ),
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_002.pak",
+                PatchKind::Generated,
+            ),
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_003.pak",
+                PatchKind::Official,
+            ),
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_004.pak",
+                PatchKind::Generated,
+            ),
+            patch(
+                "re_chunk_000.pak.sub_001.pak.patch_001.pak",
+                PatchKind::Official,
+            ),
+            patch(
+                "re_chunk_000.pak.sub_001.pak.patch_002.pak",
+                PatchKind::Generated,
+            ),
+            // generated patches are moved above third-party patches too
+            patch(
+                "re_chunk_000.pak.sub_001.pak.patch_003.pak",
+                PatchKind::ThirdParty,
+            ),
+        ];
+
+        let (renames, placeholders) = plan_renumber(&patches);
+        assert_eq!(
+            placeholders,
+            vec![patches[2].path.clone(), patches[6].path.clone()]
+        );
+        let renames = renames
+            .iter()
+            .map(|r| {
+                (
+                    r.from.file_name().unwrap().to_string_lossy().to_string(),
+                    r.to.file_name().unwrap().to_string_lossy().to_string(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            renames,
+            vec![
+                (
+                    "re_chunk_000.pak.sub_000.pak.patch_002.pak".to_string(),
+                    "re_chunk_000.pak.sub_000.pak.patch_004.pak".to_string()
+                ),
+                (
+                    "re_chunk_000.pak.sub_000.pak.patch_004.pak".to_string(),
+                    "re_chunk_000.pak.sub_000.pak.patch_005.pak".to_string()
+                ),
+                (
+                    "re_chunk_000.pak.sub_001.pak.patch_002.pak".to_string(),
+                    "re_chunk_000.pak.sub_001.pak.patch_004.pak".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_compaction() {
+        let patches = vec![
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_001.pak",
+                PatchKind::Placeholder,
+            ),
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_002.pak",
+                PatchKind::Official,
+            ),
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_003.pak",
+                PatchKind::Placeholder,
+            ),
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_004.pak",
+                PatchKind::Generated,
+            ),
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_005.pak",
+                PatchKind::Placeholder,
+            ),
+        ];
+
+        let (removals, renames) = plan_compaction(&patches);
+        assert_eq!(
+            removals,
+            vec![patches[2].path.clone(), patches[4].path.clone(),]
+        );
+        assert_eq!(
+            renames,
+            vec![Rename {
+                from: patches[3].path.clone(),
+                to: PathBuf::from("game").join("re_chunk_000.pak.sub_000.pak.patch_003.pak"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_plan_insert() {
+        let patches = vec![
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_001.pak",
+                PatchKind::Official,
+            ),
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_002.pak",
+                PatchKind::ThirdParty,
+            ),
+            patch(
+                "re_chunk_000.pak.sub_000.pak.patch_003.pak",
+                PatchKind::Generated,
+            ),
+        ];
+
+        let renames = plan_insert(&patches, 2).unwrap();
+        assert_eq!(
+            renames,
+            vec![
+                Rename {
+                    from: patches[1].path.clone(),
+                    to: patches[2].path.clone(),
+                },
+                Rename {
+                    from: patches[2].path.clone(),
+                    to: PathBuf::from("game").join("re_chunk_000.pak.sub_000.pak.patch_004.pak"),
+                },
+            ]
+        );
+        assert!(plan_insert(&patches, 4).unwrap().is_empty());
+        // official patches keep their IDs
+        assert!(plan_insert(&patches, 1).is_err());
+    }
+
+    #[test]
+    fn test_apply_renames() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let a = temp_dir.path().join("a");
+        let b = temp_dir.path().join("b");
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+
+        // swap
+        apply_renames(&[
+            Rename {
+                from: a.clone(),
+                to: b.clone(),
+            },
+            Rename {
+                from: b.clone(),
+                to: a.clone(),
+            },
+        ])
+        .unwrap();
+        assert_eq!(fs::read_to_string(&a).unwrap(), "b");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "a");
+    }
+}
@@ -0,0 +1,237 @@
+//! Game profiles.
+//!
+//! A profile bundles everything that differs between RE Engine games. Monster
+//! Hunter Wilds is built in, other games can be added with JSON files in a
+//! `profiles` directory next to the executable or in the working directory.
+//!
+//! Profile file example (`profiles/mhrise.json`, ID is the file stem):
+//!
+//! ```json
+//! {
+//!     "name": "Monster Hunter Rise",
+//!     "executable": "MonsterHunterRise.exe",
+//!     "steam_app_id": 1446780,
+//!     "name_list": "MHRise_STM.list.zst",
+//!     "tex_suffixes": [".tex.28"],
+//!     "dlc_dir": null
+//! }
+//! ```
+//!
+//! `name_list` is relative to the profile file. `display_name` is shown in the
+//! title and defaults to `name`. Chunk files must follow the usual RE Engine
+//! `re_chunk_` and `re_dlc_` naming. An optional `discovery` object configures
+//! where chunks are searched, see [`DiscoveryOptions`].
+
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::bail;
+use fs_err as fs;
+use serde::Deserialize;
+
+use crate::discovery::DiscoveryOptions;
+
+const PROFILE_DIR: &str = "profiles";
+const MHWS_NAME_LIST: &[u8] = include_bytes!("../assets/MHWs_STM_Release.list.zst");
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GameProfile {
+    /// Profile ID, the file stem of the profile file
+    #[serde(skip)]
+    pub id: String,
+    /// Game name
+    pub name: String,
+    /// Game name shown in the title, `name` if not set
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// Game executable file name
+    pub executable: String,
+    /// Steam app ID, used for game directory detection
+    #[serde(default)]
+    pub steam_app_id: Option<u32>,
+    /// File name list path
+    #[serde(default)]
+    pub name_list: Option<PathBuf>,
+    /// File name suffixes of tex files, including the version
+    pub tex_suffixes: Vec<String>,
+    /// Base chunk file name every install has
+    #[serde(default = "default_base_chunk")]
+    pub base_chunk: String,
+    /// DLC directory relative to the game directory
+    #[serde(default)]
+    pub dlc_dir: Option<String>,
+    /// Chunk discovery options
+    #[serde(default)]
+    pub discovery: DiscoveryOptions,
+    /// Embedded file name list of built-in profiles
+    #[serde(skip)]
+    embedded_name_list: Option<&'static [u8]>,
+}
+
+impl Default for GameProfile {
+    fn default() -> Self {
+        Self::mhws()
+    }
+}
+
+impl GameProfile {
+    /// Built-in Monster Hunter Wilds profile
+    pub fn mhws() -> Self {
+        Self {
+            id: "mhws".to_string(),
+            name: "Monster Hunter Wilds".to_string(),
+            display_name: Some("Monster Hunter: Wilds".to_string()),
+            executable: "MonsterHunterWilds.exe".to_string(),
+            steam_app_id: Some(2246340),
+            name_list: None,
+            tex_suffixes: vec![".tex.241106027".to_string()],
+            base_chunk: default_base_chunk(),
+            dlc_dir: Some("dlc".to_string()),
+            discovery: DiscoveryOptions::default(),
+            embedded_name_list: Some(MHWS_NAME_LIST),
+        }
+    }
+
+    /// Load a profile from a JSON file
+    pub fn from_file(path: &Path) -> color_eyre::Result<Self> {
+        let mut profile: GameProfile = serde_json::from_str(&fs::read_to_string(path)?)?;
+        profile.id = path.file_stem().unwrap().to_string_lossy().to_string();
+
+        let Some(name_list) = &profile.name_list else {
+            bail!("Profile {} has no name_list", path.display());
+        };
+        if name_list.is_relative()
+            && let Some(parent) = path.parent()
+        {
+            profile.name_list = Some(parent.join(name_list));
+        }
+        if profile.tex_suffixes.is_empty() {
+            bail!("Profile {} has no tex_suffixes", path.display());
+        }
+
+        Ok(profile)
+    }
+
+    /// All available profiles, built-in profiles first
+    pub fn discover() -> Vec<Self> {
+        let mut profiles = vec![Self::mhws()];
+
+        let mut profile_dirs = Vec::new();
+        if let Ok(exe_path) = std::env::current_exe()
+            && let Some(exe_dir) = exe_path.parent()
+        {
+            profile_dirs.push(exe_dir.join(PROFILE_DIR));
+        }
+        profile_dirs.push(PathBuf::from(PROFILE_DIR));
+
+        let mut seen_dirs = Vec::new();
+        for profile_dir in profile_dirs {
+            let Ok(canonical_dir) = fs::canonicalize(&profile_dir) else {
+                continue;
+            };
+            if seen_dirs.contains(&canonical_dir) {
+                continue;
+            }
+            seen_dirs.push(canonical_dir);
+
+            let Ok(entries) = fs::read_dir(&profile_dir) else {
+                continue;
+            };
+            let mut paths = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .collect::<Vec<_>>();
+            paths.sort();
+
+            for path in paths {
+                match Self::from_file(&path) {
+                    Ok(profile) if profiles.iter().any(|p| p.id == profile.id) => {
+                        eprintln!("Duplicated profile ID {}, skipped", profile.id);
+                    }
+                    Ok(profile) => profiles.push(profile),
+                    Err(e) => eprintln!("Failed to load profile {}: {e}", path.display()),
+                }
+            }
+        }
+
+        profiles
+    }
+
+    /// Read compressed file name list data
+    pub fn name_list_data(&self) -> color_eyre::Result<Cow<'static, [u8]>> {
+        if let Some(data) = self.embedded_name_list {
+            return Ok(Cow::Borrowed(data));
+        }
+        match &self.name_list {
+            Some(path) => Ok(Cow::Owned(fs::read(path)?)),
+            None => bail!("Profile {} has no file name list", self.id),
+        }
+    }
+
+    /// Discovery options, search roots default to the game and DLC directory
+    pub fn discovery_options(&self) -> DiscoveryOptions {
+        let mut options = self.discovery.clone();
+        if options.roots.is_empty() {
+            options.roots.push(PathBuf::from("."));
+            if let Some(dlc_dir) = &self.dlc_dir {
+                options.roots.push(PathBuf::from(dlc_dir));
+            }
+        }
+        options
+    }
+
+    pub fn tex_suffixes(&self) -> Vec<&str> {
+        self.tex_suffixes.iter().map(String::as_str).collect()
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.name)
+    }
+}
+
+fn default_base_chunk() -> String {
+    "re_chunk_000.pak".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_from_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("mhrise.json");
+        fs::write(
+            &path,
+            r#"{
+                "name": "Monster Hunter Rise",
+                "executable": "MonsterHunterRise.exe",
+                "steam_app_id": 1446780,
+                "name_list": "MHRise_STM.list.zst",
+                "tex_suffixes": [".tex.28"]
+            }"#,
+        )
+        .unwrap();
+
+        let profile = GameProfile::from_file(&path).unwrap();
+        assert_eq!(profile.id, "mhrise");
+        assert_eq!(profile.steam_app_id, Some(1446780));
+        assert_eq!(
+            profile.name_list,
+            Some(temp_dir.path().join("MHRise_STM.list.zst"))
+        );
+        assert_eq!(profile.base_chunk, "re_chunk_000.pak");
+        assert_eq!(profile.dlc_dir, None);
+        assert_eq!(profile.display_name(), "Monster Hunter Rise");
+        assert!(profile.name_list_data().is_err());
+
+        fs::write(
+            &path,
+            r#"{"name": "a", "executable": "a.exe", "tex_suffixes": []}"#,
+        )
+        .unwrap();
+        assert!(GameProfile::from_file(&path).is_err());
+    }
+}
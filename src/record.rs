@@ -0,0 +1,387 @@
+//! Record of files generated by this tool in a game directory.
+//!
+//! Generated paks carry `PakMetadata`, but once a game update overwrites one
+//! of them, the metadata is gone with it. The record keeps track of generated
+//! files so such cases can be detected.
+
+use std::{
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::ChunkName;
+
+const RECORD_FILE_NAME: &str = "tex-decompressor-record.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    version: u32,
+    #[serde(default)]
+    pub outputs: Vec<OutputRecord>,
+    /// Backup store directory of replace mode, backups are placed next to
+    /// the original files if not set
+    #[serde(default)]
+    pub backup_dir: Option<PathBuf>,
+    /// Pak files of the install before this tool touched it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub baseline: Option<Baseline>,
+}
+
+/// A generated pak file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputRecord {
+    /// Path relative to the game directory, with `/` separators
+    pub path: String,
+    pub is_full_package: bool,
+    /// Patches moved up to make room for this output, undone on restore
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shifted: Vec<ShiftRecord>,
+}
+
+/// A rename of another pak file, paths relative to the game directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShiftRecord {
+    pub from: String,
+    pub to: String,
+}
+
+/// Pak files found on the first run, or the first run after a game update.
+///
+/// Mods may already be installed when the baseline is taken, so patch files
+/// are only considered official once the user confirmed which ones are mods.
+/// Chunks that are not patches are official either way.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Baseline {
+    /// Game executable when the baseline was taken, a different one means
+    /// the game was updated
+    pub executable: Option<FileStamp>,
+    pub files: Vec<BaselineFile>,
+    /// The user confirmed the mods among the patch files
+    #[serde(default)]
+    pub verified: bool,
+    /// Files confirmed as mods, relative to the game directory
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mods: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub size: u64,
+    /// Modification time in seconds since the Unix epoch
+    pub modified: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaselineFile {
+    /// Path relative to the game directory, with `/` separators
+    pub path: String,
+    pub size: u64,
+    /// Whether the file is a tool output carrying `PakMetadata`
+    pub has_metadata: bool,
+}
+
+/// Difference between the baseline and the current install
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BaselineChange {
+    Added(BaselineFile),
+    Missing(BaselineFile),
+    Changed {
+        old: BaselineFile,
+        new: BaselineFile,
+    },
+}
+
+impl Default for Record {
+    fn default() -> Self {
+        Self {
+            version: 1,
+            outputs: Vec::new(),
+            backup_dir: None,
+            baseline: None,
+        }
+    }
+}
+
+impl Record {
+    /// Load record from the game directory, empty if not exists
+    pub fn load(game_dir: &Path) -> color_eyre::Result<Self> {
+        let path = game_dir.join(RECORD_FILE_NAME);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Save record to the game directory, the file is removed if the record is empty
+    pub fn save(&self, game_dir: &Path) -> color_eyre::Result<()> {
+        let path = game_dir.join(RECORD_FILE_NAME);
+        if self.outputs.is_empty() && self.backup_dir.is_none() && self.baseline.is_none() {
+            if path.is_file() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn add_output(&mut self, game_dir: &Path, file_path: &Path, is_full_package: bool) {
+        let path = relative_path(game_dir, file_path);
+        self.outputs.retain(|output| output.path != path);
+        self.outputs.push(OutputRecord {
+            path,
+            is_full_package,
+            shifted: Vec::new(),
+        });
+    }
+
+    /// Record renames done to make room for an output
+    pub fn set_shifted<'a>(
+        &mut self,
+        game_dir: &Path,
+        file_path: &Path,
+        renames: impl IntoIterator<Item = (&'a Path, &'a Path)>,
+    ) {
+        let path = relative_path(game_dir, file_path);
+        if let Some(output) = self.outputs.iter_mut().find(|output| output.path == path) {
+            output.shifted = renames
+                .into_iter()
+                .map(|(from, to)| ShiftRecord {
+                    from: relative_path(game_dir, from),
+                    to: relative_path(game_dir, to),
+                })
+                .collect();
+        }
+    }
+
+    pub fn output(&self, game_dir: &Path, file_path: &Path) -> Option<&OutputRecord> {
+        let path = relative_path(game_dir, file_path);
+        self.outputs.iter().find(|output| output.path == path)
+    }
+
+    /// Check if a file is an output or was shifted by one
+    pub fn is_tracked(&self, game_dir: &Path, file_path: &Path) -> bool {
+        let path = relative_path(game_dir, file_path);
+        self.outputs.iter().any(|output| {
+            output.path == path
+                || output
+                    .shifted
+                    .iter()
+                    .any(|shift| shift.from == path || shift.to == path)
+        })
+    }
+
+    /// Outputs placed below other patches keep their position
+    pub fn is_placed(&self, game_dir: &Path, file_path: &Path) -> bool {
+        self.output(game_dir, file_path)
+            .is_some_and(|output| !output.shifted.is_empty())
+    }
+
+    pub fn remove_output(&mut self, game_dir: &Path, file_path: &Path) {
+        let path = relative_path(game_dir, file_path);
+        self.outputs.retain(|output| output.path != path);
+    }
+
+    /// Rename outputs, all renames are applied at once so they may be chained
+    pub fn rename_outputs<'a>(
+        &mut self,
+        game_dir: &Path,
+        renames: impl IntoIterator<Item = (&'a Path, &'a Path)>,
+    ) {
+        let renames = renames
+            .into_iter()
+            .map(|(from, to)| (relative_path(game_dir, from), relative_path(game_dir, to)))
+            .collect::<Vec<_>>();
+        for output in self.outputs.iter_mut() {
+            if let Some((_, to)) = renames.iter().find(|(from, _)| *from == output.path) {
+                output.path = to.clone();
+            }
+        }
+    }
+}
+
+impl Baseline {
+    pub fn new(executable: &Path, files: Vec<BaselineFile>) -> Self {
+        Self {
+            executable: FileStamp::read(executable),
+            files,
+            verified: false,
+            mods: Vec::new(),
+        }
+    }
+
+    /// Patch files without metadata, the user has to confirm which are mods
+    pub fn unconfirmed_patches(&self) -> Vec<&str> {
+        self.files
+            .iter()
+            .filter(|file| !file.has_metadata && is_patch_path(&file.path))
+            .map(|file| file.path.as_str())
+            .collect()
+    }
+
+    /// Mark the baseline as confirmed, with the given files being mods
+    pub fn verify(&mut self, mods: Vec<String>) {
+        self.mods = mods;
+        self.verified = true;
+    }
+
+    /// The game was updated since the baseline was taken
+    pub fn is_outdated(&self, executable: &Path) -> bool {
+        self.executable != FileStamp::read(executable)
+    }
+
+    /// Check if a file was in the baseline without metadata, and is not a mod.
+    /// Patch files of an unconfirmed baseline are never official.
+    pub fn is_official(&self, game_dir: &Path, file_path: &Path) -> bool {
+        let path = relative_path(game_dir, file_path);
+        if self.mods.contains(&path) || (!self.verified && is_patch_path(&path)) {
+            return false;
+        }
+        self.files
+            .iter()
+            .any(|file| file.path == path && !file.has_metadata)
+    }
+
+    /// Compare the baseline with the current files
+    pub fn changes(&self, current: &[BaselineFile]) -> Vec<BaselineChange> {
+        let mut changes = Vec::new();
+        for file in current {
+            match self.files.iter().find(|old| old.path == file.path) {
+                None => changes.push(BaselineChange::Added(file.clone())),
+                Some(old) if old != file => changes.push(BaselineChange::Changed {
+                    old: old.clone(),
+                    new: file.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for old in &self.files {
+            if !current.iter().any(|file| file.path == old.path) {
+                changes.push(BaselineChange::Missing(old.clone()));
+            }
+        }
+        changes
+    }
+}
+
+impl FileStamp {
+    pub fn read(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs()),
+        })
+    }
+}
+
+impl BaselineFile {
+    pub fn new(game_dir: &Path, file_path: &Path, size: u64, has_metadata: bool) -> Self {
+        Self {
+            path: relative_path(game_dir, file_path),
+            size,
+            has_metadata,
+        }
+    }
+}
+
+impl BaselineChange {
+    pub fn path(&self) -> &str {
+        match self {
+            BaselineChange::Added(file) | BaselineChange::Missing(file) => &file.path,
+            BaselineChange::Changed { new, .. } => &new.path,
+        }
+    }
+}
+
+impl std::fmt::Display for BaselineChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BaselineChange::Added(file) => write!(f, "{} was added", file.path),
+            BaselineChange::Missing(file) => write!(f, "{} is missing", file.path),
+            BaselineChange::Changed { old, new } if old.has_metadata != new.has_metadata => {
+                let state = if new.has_metadata { "gained" } else { "lost" };
+                write!(f, "{} {state} tool metadata", new.path)
+            }
+            BaselineChange::Changed { old, new } => write!(
+                f,
+                "{} changed size from {} to {} bytes",
+                new.path, old.size, new.size
+            ),
+        }
+    }
+}
+
+impl OutputRecord {
+    pub fn full_path(&self, game_dir: &Path) -> PathBuf {
+        game_dir.join(&self.path)
+    }
+}
+
+/// Check if a relative path names a patch chunk
+fn is_patch_path(path: &str) -> bool {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    ChunkName::try_from_str(file_name).is_ok_and(|chunk_name| chunk_name.is_patch())
+}
+
+fn relative_path(game_dir: &Path, file_path: &Path) -> String {
+    file_path
+        .strip_prefix(game_dir)
+        .unwrap_or(file_path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_baseline_changes() {
+        let file = |path: &str, size: u64, has_metadata: bool| BaselineFile {
+            path: path.to_string(),
+            size,
+            has_metadata,
+        };
+        let baseline = Baseline {
+            executable: None,
+            verified: true,
+            mods: Vec::new(),
+            files: vec![
+                file("re_chunk_000.pak", 100, false),
+                file("re_chunk_000.pak.patch_001.pak", 10, false),
+                file("re_chunk_000.pak.patch_002.pak", 20, false),
+            ],
+        };
+        let game_dir = Path::new("game");
+        assert!(baseline.is_official(game_dir, &game_dir.join("re_chunk_000.pak")));
+        assert!(!baseline.is_official(game_dir, &game_dir.join("re_chunk_000.pak.patch_003.pak")));
+
+        let changes = baseline.changes(&[
+            file("re_chunk_000.pak", 100, false),
+            file("re_chunk_000.pak.patch_001.pak", 15, false),
+            file("re_chunk_000.pak.patch_003.pak", 5, false),
+        ]);
+        assert_eq!(
+            changes,
+            vec![
+                BaselineChange::Changed {
+                    old: file("re_chunk_000.pak.patch_001.pak", 10, false),
+                    new: file("re_chunk_000.pak.patch_001.pak", 15, false),
+                },
+                BaselineChange::Added(file("re_chunk_000.pak.patch_003.pak", 5, false)),
+                BaselineChange::Missing(file("re_chunk_000.pak.patch_002.pak", 20, false)),
+            ]
+        );
+        assert_eq!(
+            changes[0].to_string(),
+            "re_chunk_000.pak.patch_001.pak changed size from 10 to 15 bytes"
+        );
+    }
+}
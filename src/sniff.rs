@@ -0,0 +1,68 @@
+//! File type detection by magic bytes.
+
+use std::io::{self, Read};
+
+/// Number of leading bytes needed to detect a file type.
+pub const MAGIC_LEN: usize = 8;
+
+/// Known RE Engine resource magics, matched against the start of the file.
+const MAGICS: &[(&[u8], &str)] = &[
+    (b"TEX\0", "tex"),
+    (b"MESH", "mesh"),
+    (b"MDF\0", "mdf2"),
+    (b"RSZ\0", "user"),
+    (b"USR\0", "user"),
+    (b"SCN\0", "scn"),
+    (b"PFB\0", "pfb"),
+    (b"GUI\0", "gui"),
+    (b"MOTLIST\0", "motlist"),
+    (b"mlst", "motlist"),
+    (b"mot\0", "mot"),
+    (b"mcam", "mcamlist"),
+    (b"efxr", "efx"),
+    (b"CHF\0", "chain"),
+    (b"BNK\0", "bnk"),
+    (b"BKHD", "bnk"),
+    (b"AKPK", "pck"),
+    (b"DDS ", "dds"),
+    (b"\x89PNG", "png"),
+    (b"RIFF", "riff"),
+    (b"OggS", "ogg"),
+    (b"\x1aE\xdf\xa3", "webm"),
+    (b"USM\0", "usm"),
+    (b"CRID", "usm"),
+    (b"MSG\0", "msg"),
+    (b"GMSG", "msg"),
+];
+
+/// Detect file type from the leading bytes of a file.
+pub fn detect_file_type(data: &[u8]) -> Option<&'static str> {
+    MAGICS
+        .iter()
+        .find(|(magic, _)| data.starts_with(magic))
+        .map(|(_, file_type)| *file_type)
+}
+
+/// Read the leading bytes of a file and detect its type.
+pub fn sniff_reader<R>(reader: &mut R) -> io::Result<Option<&'static str>>
+where
+    R: Read,
+{
+    let mut buf = Vec::with_capacity(MAGIC_LEN);
+    reader.take(MAGIC_LEN as u64).read_to_end(&mut buf)?;
+    Ok(detect_file_type(&buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_file_type() {
+        assert_eq!(detect_file_type(b"TEX\0\x1b\x8f\x62\x0e"), Some("tex"));
+        assert_eq!(detect_file_type(b"MESH\0\0\0\0"), Some("mesh"));
+        assert_eq!(detect_file_type(b"MOTLIST\0"), Some("motlist"));
+        assert_eq!(detect_file_type(b"TE"), None);
+        assert_eq!(detect_file_type(b"\0\0\0\0\0\0\0\0"), None);
+    }
+}
@@ -0,0 +1,322 @@
+//! Game install detection from Steam library metadata.
+//!
+//! Steam keeps a list of library folders in `steamapps/libraryfolders.vdf`,
+//! and every installed app has a `steamapps/appmanifest_<appid>.acf` in its
+//! library, with the install directory name under `steamapps/common`.
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{bail, eyre};
+use fs_err as fs;
+
+/// Environment variable for extra Steam roots, separated like `PATH`
+const STEAM_ROOT_ENV: &str = "MHWS_STEAM_ROOT";
+
+/// Valve KeyValues (VDF) node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Vdf {
+    Value(String),
+    Object(Vec<(String, Vdf)>),
+}
+
+impl Vdf {
+    /// Parse VDF text into a root object
+    pub fn parse(text: &str) -> color_eyre::Result<Self> {
+        let tokens = tokenize(text)?;
+        let mut pos = 0;
+        let root = parse_object(&tokens, &mut pos)?;
+        if pos != tokens.len() {
+            bail!("Unexpected closing brace in VDF");
+        }
+        Ok(root)
+    }
+
+    /// Get child node by key, keys are case-insensitive
+    pub fn get(&self, key: &str) -> Option<&Vdf> {
+        match self {
+            Vdf::Object(children) => children
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v),
+            Vdf::Value(_) => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Vdf::Value(value) => Some(value),
+            Vdf::Object(_) => None,
+        }
+    }
+
+    pub fn children(&self) -> &[(String, Vdf)] {
+        match self {
+            Vdf::Object(children) => children,
+            Vdf::Value(_) => &[],
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    String(String),
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> color_eyre::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(c) => value.push(c),
+                            None => bail!("Unterminated string in VDF"),
+                        },
+                        Some(c) => value.push(c),
+                        None => bail!("Unterminated string in VDF"),
+                    }
+                }
+                tokens.push(Token::String(value));
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                // line comment
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                // unquoted token
+                let mut value = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '{' || c == '}' || c == '"' {
+                        break;
+                    }
+                    value.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::String(value));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_object(tokens: &[Token], pos: &mut usize) -> color_eyre::Result<Vdf> {
+    let mut children = Vec::new();
+    while let Some(token) = tokens.get(*pos) {
+        let key = match token {
+            Token::String(key) => key.clone(),
+            Token::Close => break,
+            Token::Open => bail!("Unexpected opening brace in VDF"),
+        };
+        *pos += 1;
+
+        let value = match tokens.get(*pos) {
+            Some(Token::String(value)) => {
+                *pos += 1;
+                Vdf::Value(value.clone())
+            }
+            Some(Token::Open) => {
+                *pos += 1;
+                let object = parse_object(tokens, pos)?;
+                if tokens.get(*pos) != Some(&Token::Close) {
+                    bail!("Unclosed object '{key}' in VDF");
+                }
+                *pos += 1;
+                object
+            }
+            _ => bail!("Missing value for key '{key}' in VDF"),
+        };
+        children.push((key, value));
+    }
+
+    Ok(Vdf::Object(children))
+}
+
+/// Steam root directories to search, user configured roots first
+pub fn default_steam_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+
+    if let Some(env_roots) = std::env::var_os(STEAM_ROOT_ENV) {
+        roots.extend(std::env::split_paths(&env_roots));
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        roots.push(PathBuf::from(r"C:\Program Files (x86)\Steam"));
+        roots.push(PathBuf::from(r"C:\Program Files\Steam"));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        if let Some(home) = std::env::var_os("HOME") {
+            let home = PathBuf::from(home);
+            roots.push(home.join(".steam/steam"));
+            roots.push(home.join(".local/share/Steam"));
+            // Flatpak
+            roots.push(home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"));
+            // Snap
+            roots.push(home.join("snap/steam/common/.local/share/Steam"));
+        }
+    }
+
+    roots
+}
+
+/// Library folders of a Steam root, including the root itself
+pub fn library_folders(steam_root: &Path) -> Vec<PathBuf> {
+    let mut folders = vec![steam_root.to_path_buf()];
+
+    for vdf_path in [
+        steam_root.join("steamapps").join("libraryfolders.vdf"),
+        steam_root.join("config").join("libraryfolders.vdf"),
+    ] {
+        let Ok(text) = fs::read_to_string(&vdf_path) else {
+            continue;
+        };
+        let Ok(vdf) = Vdf::parse(&text) else {
+            eprintln!("Failed to parse {}", vdf_path.display());
+            continue;
+        };
+        let Some(root) = vdf.get("libraryfolders") else {
+            continue;
+        };
+
+        // library entries use numeric keys, skip other fields like "ContentStatsID"
+        for (_, folder) in root
+            .children()
+            .iter()
+            .filter(|(key, _)| key.parse::<u32>().is_ok())
+        {
+            // old format stores the path directly, new format stores it in an object
+            let path = folder
+                .as_str()
+                .or_else(|| folder.get("path").and_then(Vdf::as_str));
+            if let Some(path) = path {
+                folders.push(PathBuf::from(path));
+            }
+        }
+    }
+
+    folders
+}
+
+/// Get install directory of an app in a library folder
+pub fn app_install_dir(library: &Path, app_id: u32) -> color_eyre::Result<PathBuf> {
+    let steamapps = library.join("steamapps");
+    let manifest_path = steamapps.join(format!("appmanifest_{app_id}.acf"));
+    let manifest = Vdf::parse(&fs::read_to_string(&manifest_path)?)?;
+
+    let install_dir = manifest
+        .get("AppState")
+        .and_then(|app_state| app_state.get("installdir"))
+        .and_then(Vdf::as_str)
+        .ok_or_else(|| eyre!("No installdir in {}", manifest_path.display()))?;
+
+    Ok(steamapps.join("common").join(install_dir))
+}
+
+/// Find all install directories of an app from the given Steam roots
+pub fn find_app_install_dirs(steam_roots: &[PathBuf], app_id: u32) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = Vec::new();
+    let mut seen = Vec::new();
+
+    for steam_root in steam_roots.iter().filter(|root| root.is_dir()) {
+        for library in library_folders(steam_root) {
+            let Ok(install_dir) = app_install_dir(&library, app_id) else {
+                continue;
+            };
+            if !install_dir.is_dir() {
+                continue;
+            }
+            // the same library may be reached via symlinks, e.g. ~/.steam/steam
+            let canonical = fs::canonicalize(&install_dir).unwrap_or_else(|_| install_dir.clone());
+            if !seen.contains(&canonical) {
+                seen.push(canonical);
+                found.push(install_dir);
+            }
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MHWILDS_APP_ID: u32 = 2246340;
+
+    #[test]
+    fn test_parse_vdf() {
+        let text = r#"
+"libraryfolders"
+{
+    // comment
+    "0"
+    {
+        "path"      "C:\\Program Files (x86)\\Steam"
+        "apps"
+        {
+            "2246340"       "123"
+        }
+    }
+    "1"     "D:\\SteamLibrary"
+}
+"#;
+        let vdf = Vdf::parse(text).unwrap();
+        let folders = vdf.get("libraryfolders").unwrap();
+        assert_eq!(folders.children().len(), 2);
+        assert_eq!(
+            folders.get("0").unwrap().get("path").unwrap().as_str(),
+            Some(r"C:\Program Files (x86)\Steam")
+        );
+        assert_eq!(folders.get("1").unwrap().as_str(), Some(r"D:\SteamLibrary"));
+
+        assert!(Vdf::parse("\"a\" { \"b\" \"c\"").is_err());
+    }
+
+    #[test]
+    fn test_find_app_install_dirs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let steam_root = temp_dir.path().join("Steam");
+        let library = temp_dir.path().join("Library");
+        fs::create_dir_all(steam_root.join("steamapps")).unwrap();
+        fs::create_dir_all(library.join("steamapps/common/MonsterHunterWilds")).unwrap();
+
+        let library_vdf = format!(
+            "\"libraryfolders\"\n{{\n\t\"0\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n\t\"1\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n}}\n",
+            steam_root.display().to_string().replace('\\', "\\\\"),
+            library.display().to_string().replace('\\', "\\\\"),
+        );
+        fs::write(steam_root.join("steamapps/libraryfolders.vdf"), library_vdf).unwrap();
+        fs::write(
+            library.join(format!("steamapps/appmanifest_{MHWILDS_APP_ID}.acf")),
+            "\"AppState\"\n{\n\t\"appid\"\t\t\"2246340\"\n\t\"installdir\"\t\t\"MonsterHunterWilds\"\n}\n",
+        )
+        .unwrap();
+
+        let found = find_app_install_dirs(&[steam_root.clone()], MHWILDS_APP_ID);
+        assert_eq!(
+            found,
+            vec![library.join("steamapps/common").join("MonsterHunterWilds")]
+        );
+
+        assert!(find_app_install_dirs(&[steam_root], 1).is_empty());
+    }
+}
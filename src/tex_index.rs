@@ -0,0 +1,168 @@
+//! Cached index of texture file hashes.
+//!
+//! Building a full `FileNameTable` is only needed when we want file names,
+//! processing only needs to know whether a hash is a texture. The index is a
+//! sorted hash array, cached on disk so later runs skip parsing the list.
+//!
+//! Cache file layout (little endian):
+//! - magic: `[u8; 8]`
+//! - version: u32
+//! - reserved: u32
+//! - source key: u64, identifies the list and suffixes the index was built from
+//! - count: u64
+//! - hashes: `[u64; count]`, sorted
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::bail;
+use fs_err as fs;
+use ree_pak_core::utf16_hash::Utf16HashExt;
+
+use crate::name_list;
+
+const INDEX_MAGIC: &[u8; 8] = b"TEXIDX\0\0";
+const INDEX_VERSION: u32 = 1;
+const HEADER_SIZE: usize = 32;
+
+pub struct TexIndex {
+    key: u64,
+    hashes: Vec<u64>,
+}
+
+impl TexIndex {
+    /// Build index from a compressed name list, keeping paths with the given suffixes.
+    pub fn build(list_data: &[u8], tex_suffixes: &[&str]) -> color_eyre::Result<Self> {
+        let mut hashes = name_list::read_list(list_data)?
+            .into_iter()
+            .filter(|path| tex_suffixes.iter().any(|suffix| path.ends_with(suffix)))
+            .map(|path| path.as_str().hash_mixed())
+            .collect::<Vec<_>>();
+        hashes.sort_unstable();
+        hashes.dedup();
+
+        Ok(Self {
+            key: source_key(list_data, tex_suffixes),
+            hashes,
+        })
+    }
+
+    /// Load index from cache file, or build and save it if the cache is missing or stale.
+    pub fn load_or_build(
+        cache_path: &Path,
+        list_data: &[u8],
+        tex_suffixes: &[&str],
+    ) -> color_eyre::Result<Self> {
+        let key = source_key(list_data, tex_suffixes);
+        if let Ok(data) = fs::read(cache_path)
+            && let Ok(index) = Self::from_bytes(&data)
+            && index.key == key
+        {
+            return Ok(index);
+        }
+
+        let index = Self::build(list_data, tex_suffixes)?;
+        let save_result = cache_path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(cache_path, index.to_bytes()));
+        if let Err(e) = save_result {
+            eprintln!("Failed to save tex index cache: {e}");
+        }
+
+        Ok(index)
+    }
+
+    /// Cache file path of a game profile
+    pub fn cache_path(profile_id: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(env!("CARGO_PKG_NAME"))
+            .join(format!("tex_index_{profile_id}.bin"))
+    }
+
+    pub fn contains(&self, hash: u64) -> bool {
+        self.hashes.binary_search(&hash).is_ok()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> color_eyre::Result<Self> {
+        if data.len() < HEADER_SIZE || &data[0..8] != INDEX_MAGIC {
+            bail!("Invalid tex index file");
+        }
+        let version = u32::from_le_bytes(data[8..12].try_into()?);
+        if version != INDEX_VERSION {
+            bail!("Unsupported tex index version: {version}");
+        }
+        let key = u64::from_le_bytes(data[16..24].try_into()?);
+        let count = u64::from_le_bytes(data[24..32].try_into()?) as usize;
+
+        let body = &data[HEADER_SIZE..];
+        if body.len() != count * 8 {
+            bail!("Tex index file is truncated");
+        }
+        let hashes = body
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        if !hashes.is_sorted() {
+            bail!("Tex index file is not sorted");
+        }
+
+        Ok(Self { key, hashes })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(HEADER_SIZE + self.hashes.len() * 8);
+        data.extend_from_slice(INDEX_MAGIC);
+        data.extend_from_slice(&INDEX_VERSION.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&self.key.to_le_bytes());
+        data.extend_from_slice(&(self.hashes.len() as u64).to_le_bytes());
+        for hash in &self.hashes {
+            data.extend_from_slice(&hash.to_le_bytes());
+        }
+        data
+    }
+}
+
+/// FNV-1a hash of the list data and suffixes.
+fn source_key(list_data: &[u8], tex_suffixes: &[&str]) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    let mut hash = FNV_OFFSET;
+    let suffix_bytes = tex_suffixes
+        .iter()
+        .flat_map(|suffix| suffix.bytes().chain([0]));
+    for byte in list_data.iter().copied().chain(suffix_bytes) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_and_roundtrip() {
+        let paths = vec![
+            "natives/STM/a.tex.241106027".to_string(),
+            "natives/STM/b.mesh.2109108288".to_string(),
+            "natives/STM/c.tex.241106027".to_string(),
+        ];
+        let list_data = name_list::write_list(&[], &paths).unwrap();
+
+        let index = TexIndex::build(&list_data, &[".tex.241106027"]).unwrap();
+        assert_eq!(index.hashes.len(), 2);
+        assert!(index.contains("natives/STM/a.tex.241106027".hash_mixed()));
+        assert!(!index.contains("natives/STM/b.mesh.2109108288".hash_mixed()));
+
+        let loaded = TexIndex::from_bytes(&index.to_bytes()).unwrap();
+        assert_eq!(loaded.key, index.key);
+        assert_eq!(loaded.hashes, index.hashes);
+
+        // different suffixes must not share a cache
+        let other = TexIndex::build(&list_data, &[".tex"]).unwrap();
+        assert_ne!(other.key, index.key);
+    }
+}
@@ -0,0 +1,73 @@
+//! Report of pak entries whose hash cannot be resolved by the file name table.
+
+use std::{io, path::Path};
+
+use fs_err as fs;
+use ree_pak_core::{filename::FileNameTable, read::archive::PakArchiveReader};
+use serde::Serialize;
+
+use crate::sniff;
+
+#[derive(Debug, Default, Serialize)]
+pub struct UnknownHashReport {
+    pub paks: Vec<PakUnknownEntries>,
+}
+
+impl UnknownHashReport {
+    pub fn total_unknown(&self) -> usize {
+        self.paks.iter().map(|pak| pak.entries.len()).sum()
+    }
+}
+
+/// Unknown entries of a single pak file.
+#[derive(Debug, Serialize)]
+pub struct PakUnknownEntries {
+    pub pak: String,
+    pub total_entries: usize,
+    pub entries: Vec<UnknownEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnknownEntry {
+    /// Entry hash in hex, as used by file name lists
+    pub hash: String,
+    pub compressed_size: u64,
+    pub size: u64,
+    /// File type detected by magic, None if unrecognized
+    pub file_type: Option<&'static str>,
+}
+
+/// Collect all entries in a pak file that are not resolvable by the file name table.
+pub fn scan_pak(
+    path: &Path,
+    filename_table: &FileNameTable,
+) -> color_eyre::Result<PakUnknownEntries> {
+    let file = fs::File::open(path)?;
+    let mut reader = io::BufReader::new(file);
+
+    let pak_archive = ree_pak_core::read::read_archive(&mut reader)?;
+    let mut archive_reader = PakArchiveReader::new(reader, &pak_archive);
+
+    let mut entries = Vec::new();
+    for entry in pak_archive.entries() {
+        if filename_table.get_file_name(entry.hash()).is_some() {
+            continue;
+        }
+
+        let mut entry_reader = archive_reader.owned_entry_reader(entry.clone())?;
+        let file_type = sniff::sniff_reader(&mut entry_reader)?;
+
+        entries.push(UnknownEntry {
+            hash: format!("{:016x}", entry.hash()),
+            compressed_size: entry.compressed_size(),
+            size: entry.decompressed_size(),
+            file_type,
+        });
+    }
+
+    Ok(PakUnknownEntries {
+        pak: path.file_name().unwrap().to_string_lossy().to_string(),
+        total_entries: pak_archive.entries().len(),
+        entries,
+    })
+}
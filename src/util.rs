@@ -3,3 +3,38 @@ use indicatif::HumanBytes;
 pub fn human_bytes(bytes: u64) -> String {
     HumanBytes(bytes).to_string()
 }
+
+/// Format seconds since the Unix epoch as `YYYY-MM-DD HH:MM` in UTC
+pub fn format_unix_time(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let minutes = (secs % 86400) / 60;
+
+    // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}",
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_unix_time() {
+        assert_eq!(format_unix_time(0), "1970-01-01 00:00");
+        assert_eq!(format_unix_time(951782400), "2000-02-29 00:00");
+        assert_eq!(format_unix_time(1740787200 + 3660), "2025-03-01 01:01");
+    }
+}